@@ -54055,7 +54055,7 @@ function VS(E, t) {
     case "bust":
       return { type: e[0], player: e[1], dice: o };
     case "win":
-      return { type: e[0], player: e[1], score: o };
+      return { type: e[0], player: e[1], score: o[0], ratingGain: o[1], ratingLoss: o[2] };
     default:
       throw E;
   }
@@ -0,0 +1,179 @@
+//! Bot opponents, played by strategy contracts.
+//!
+//! Any contract implementing `FarkleStrategy` can be registered by the admin
+//! to take a seat in a match, under its own address. Since a contract can't
+//! sign for itself, anyone may call `advance_bot` when it's a bot's turn:
+//! the game asks the strategy what to do with each roll, and plays its
+//! answer through the same validation as `roll`.
+//!
+//! Matches with a bot are never wagered and earn no rewards.
+
+use soroban_sdk::{contractclient, contractimpl, contracttype, panic_with_error};
+use soroban_sdk::{Address, Env, Vec};
+
+use crate::{persist, AdminData, Error, Farkle, FarkleArgs, FarkleClient, MatchTerms, UserData};
+
+// Caps how many rolls one `advance_bot` call will play for a bot.
+const MAX_STEPS: u32 = 12;
+
+/** What a strategy gets to see when it's asked to decide on a roll. */
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct BotState {
+    pub dice: Vec<u32>,  // the roll to decide on
+    pub turn_score: u32, // points set aside so far this turn
+    pub score: u32,      // points banked in earlier turns
+    pub target: u32,     // score needed to win
+    pub opp_score: u32,
+    pub opp_target: u32,
+}
+
+/**
+ * The interface a bot's contract implements.
+ *
+ * `decide` answers a roll just like the `save` and `stop` arguments of
+ * `roll`: which dice (by index) to keep, and whether to bank afterwards.
+ */
+#[contractclient(name = "FarkleStrategyClient")]
+pub trait FarkleStrategy {
+    fn decide(env: Env, state: BotState) -> (Vec<u32>, bool);
+}
+
+#[contractimpl]
+impl Farkle {
+    /** Allows (or, with `registered` unset, stops) a strategy contract to play. */
+    pub fn register_bot(env: Env, bot: Address, registered: bool) {
+        Self::check_init(&env);
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        let store = env.storage().persistent();
+        let key = UserData::Bot(bot);
+        if registered {
+            persist(&env, &key, &true);
+        } else if store.has(&key) {
+            store.remove(&key);
+        }
+    }
+
+    /** Returns whether `bot` is a registered strategy contract. */
+    pub fn is_bot(env: Env, bot: Address) -> bool {
+        is(&env, &bot)
+    }
+
+    /**
+     * Begins an unwagered match between a player and a bot.
+     *
+     * # Returns
+     *
+     * The address of whoever should go first.
+     *
+     * # Panics
+     *
+     * - If `bot` isn't registered.
+     * - If either of them is already in a game.
+     */
+    pub fn engage_bot(env: Env, player: Address, bot: Address) -> Address {
+        Self::check_init(&env);
+        check(&env, &bot);
+        Self::check_free(&env, &player);
+        Self::check_free(&env, &bot);
+        player.require_auth();
+
+        let first: u64 = env.prng().gen_range(1..=2);
+        Self::start_match(&env, &player, &bot, first == 1, &MatchTerms::even(0))
+    }
+
+    /**
+     * Begins an unwagered match between two bots. Anyone may set this up,
+     * since neither side has anything to lose.
+     *
+     * # Panics
+     *
+     * - If either bot isn't registered, or they're the same bot.
+     * - If either of them is already in a game.
+     */
+    pub fn engage_bots(env: Env, a: Address, b: Address) -> Address {
+        Self::check_init(&env);
+        if a == b {
+            panic_with_error!(&env, Error::WrongMatch);
+        }
+        check(&env, &a);
+        check(&env, &b);
+        Self::check_free(&env, &a);
+        Self::check_free(&env, &b);
+
+        let first: u64 = env.prng().gen_range(1..=2);
+        Self::start_match(&env, &a, &b, first == 1, &MatchTerms::even(0))
+    }
+
+    /**
+     * Plays a bot's turn, asking its strategy contract what to do with each
+     * roll. Anyone may call this.
+     *
+     * # Returns
+     *
+     * Whether it's still the bot's turn, if it rolled more than one call's
+     * worth of times.
+     *
+     * # Panics
+     *
+     * - If `bot` isn't registered, or it isn't its turn.
+     * - If the strategy fails, or gives an answer `roll` would reject.
+     */
+    pub fn advance_bot(env: Env, bot: Address) -> bool {
+        Self::check_init(&env);
+        check(&env, &bot);
+
+        let store = env.storage().temporary();
+        let strategy = FarkleStrategyClient::new(&env, &bot);
+
+        for _ in 0..MAX_STEPS {
+            let opp = Self::check_turn(&env, &bot);
+            let dice: Vec<u32> = store
+                .get(&UserData::Dice(bot.clone()))
+                .unwrap_or(Vec::new(&env));
+
+            // A turn always starts with a fresh roll; there's nothing to decide.
+            let (save, stop) = if dice.is_empty() {
+                (Vec::new(&env), false)
+            } else {
+                strategy.decide(&BotState {
+                    dice,
+                    turn_score: store.get(&UserData::TurnScore(bot.clone())).unwrap_or(0),
+                    score: Self::banked(&env, &bot, &opp),
+                    target: Self::target(&env, &bot),
+                    opp_score: Self::banked(&env, &opp, &bot),
+                    opp_target: Self::target(&env, &opp),
+                })
+            };
+
+            Self::take_roll(&env, bot.clone(), opp, save, stop);
+
+            // Stop once the turn passes, or the match is over.
+            if !store.has(&UserData::Match(bot.clone())) {
+                return false;
+            }
+            let turn: Address = store.get(&UserData::Turn(bot.clone())).unwrap();
+            if turn != bot {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/** Whether `player` is a registered strategy contract. */
+pub fn is(env: &Env, player: &Address) -> bool {
+    env.storage()
+        .persistent()
+        .has(&UserData::Bot(player.clone()))
+}
+
+fn check(env: &Env, bot: &Address) {
+    if !is(env, bot) {
+        panic_with_error!(env, Error::NotABot);
+    }
+}
@@ -0,0 +1,354 @@
+//! Timed high-score contests, played solo for a prize pool.
+//!
+//! The admin opens a contest with an entry fee, a number of turns, a closing
+//! ledger and a prize schedule. Each entry plays its turns alone (see the
+//! `solo` module), and a player's best total across their entries goes on
+//! the leaderboard. Once the contest closes, anyone can have the pool paid
+//! out to the top of the leaderboard.
+
+use soroban_sdk::{contractevent, contractimpl, contracttype, panic_with_error, token};
+use soroban_sdk::{Address, Env, Vec};
+
+use crate::solo::{self, Run};
+use crate::{persist, AdminData, ContestData, Error, Farkle, FarkleArgs, FarkleClient};
+
+const MAX_TURNS: u32 = 20;
+const MAX_PLACES: u32 = 10;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Contest {
+    pub id: u64,
+    pub fee: i128,              // paid for every entry
+    pub turns: u32,             // how many turns each entry plays
+    pub closes: u32,            // ledger after which no more rolls count
+    pub payouts: Vec<u32>,      // per-mille share of the pool, by place
+    pub pool: i128,             // entry fees, less the usual fee
+    pub entries: u32,           // how many times anyone has entered
+    pub leaders: Vec<Standing>, // best first, as many as there are places
+    pub paid: bool,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Standing {
+    pub player: Address,
+    pub score: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ContestEntry {
+    pub run: Run,      // the entry being played, or the last one finished
+    pub playing: bool, // whether `run` still has turns left
+    pub finished: u32, // how many entries were played to the end
+    pub best: u32,     // best total across them
+}
+
+#[contractevent(topics = ["contest_score"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ContestScoreEvent {
+    #[topic]
+    id: u64,
+
+    #[topic]
+    player: Address,
+
+    score: u32,
+    best: u32,
+}
+
+#[contractevent(topics = ["contest_prize"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ContestPrizeEvent {
+    #[topic]
+    id: u64,
+
+    #[topic]
+    player: Address,
+
+    place: u32,
+    prize: i128,
+}
+
+#[contractimpl]
+impl Farkle {
+    /**
+     * Opens a new high-score contest.
+     *
+     * # Arguments
+     *
+     * `fee` - What each entry costs, paid from the player's balance.
+     * `turns` - How many turns each entry plays, up to 20.
+     * `closes` - The ledger after which the contest is over.
+     * `payouts` - The share of the pool (per mille, adding up to 1000) for
+     *      each place on the leaderboard, from first down. First place must
+     *      get some of it. If fewer players finish than there are places, the
+     *      shares of those who did are scaled up to cover the whole pool.
+     *
+     * # Returns
+     *
+     * The identifier of the contest.
+     */
+    pub fn open_contest(env: Env, fee: i128, turns: u32, closes: u32, payouts: Vec<u32>) -> u64 {
+        Self::check_init(&env);
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        if fee <= 0
+            || !(1..=MAX_TURNS).contains(&turns)
+            || closes <= env.ledger().sequence()
+            || payouts.first().unwrap_or(0) == 0
+            || payouts.len() > MAX_PLACES
+            || payouts.iter().sum::<u32>() != 1000
+        {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        let instance = env.storage().instance();
+        let id: u64 = instance.get(&ContestData::Count).unwrap_or(0);
+        instance.set(&ContestData::Count, &(id + 1));
+
+        persist(
+            &env,
+            &ContestData::Contest(id),
+            &Contest {
+                id,
+                fee,
+                turns,
+                closes,
+                payouts,
+                pool: 0,
+                entries: 0,
+                leaders: Vec::new(&env),
+                paid: false,
+            },
+        );
+        id
+    }
+
+    /**
+     * Pays a contest's entry fee and starts a fresh entry.
+     *
+     * A player can enter as many times as they like, but only once the
+     * entry before has played all of its turns.
+     *
+     * # Panics
+     *
+     * - If the contest has closed.
+     * - If the player's last entry is still being played.
+     * - If they don't have the fee deposited.
+     */
+    pub fn enter_contest(env: Env, player: Address, id: u64) {
+        Self::check_init(&env);
+        let mut contest = open(&env, id);
+
+        let key = ContestData::Entry(id, player.clone());
+        let mut entry = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .unwrap_or(ContestEntry {
+                run: Run::new(&env),
+                playing: false,
+                finished: 0,
+                best: 0,
+            });
+        if entry.playing {
+            panic_with_error!(&env, Error::AlreadyPlaying);
+        }
+
+        player.require_auth();
+        Self::hold_balance(&env, &player, contest.fee);
+
+        contest.pool += contest.fee - Self::get_fee(contest.fee);
+        contest.entries += 1;
+        persist(&env, &ContestData::Contest(id), &contest);
+
+        entry.run = Run::new(&env);
+        entry.playing = true;
+        persist(&env, &key, &entry);
+    }
+
+    /**
+     * Plays a roll of the player's current contest entry.
+     *
+     * This works just like `roll`, except that banking or busting moves
+     * straight on to the player's next turn. Once the last turn is over, the
+     * entry's total counts toward the leaderboard.
+     *
+     * # Panics
+     *
+     * - If the contest has closed.
+     * - If the player has no entry being played.
+     * - For any of the reasons `roll` would.
+     */
+    pub fn contest_roll(
+        env: Env,
+        player: Address,
+        id: u64,
+        save: Vec<u32>,
+        stop: bool,
+    ) -> Vec<u32> {
+        Self::check_init(&env);
+        let mut contest = open(&env, id);
+
+        let key = ContestData::Entry(id, player.clone());
+        let mut entry: ContestEntry = match env.storage().persistent().get(&key) {
+            Some(entry) => entry,
+            None => panic_with_error!(&env, Error::WrongMatch),
+        };
+        if !entry.playing {
+            panic_with_error!(&env, Error::WrongMatch);
+        }
+
+        player.require_auth();
+
+        let roll = solo::step(&env, &mut entry.run, save, stop, |count| {
+            Self::roll_dice(&env, count)
+        });
+
+        if entry.run.turns >= contest.turns {
+            entry.playing = false;
+            entry.finished += 1;
+            if entry.run.score > entry.best || entry.finished == 1 {
+                entry.best = entry.run.score;
+                let places = contest.payouts.len();
+                rank(&mut contest.leaders, places, &player, entry.best);
+                persist(&env, &ContestData::Contest(id), &contest);
+            }
+
+            ContestScoreEvent {
+                id,
+                player: player.clone(),
+                score: entry.run.score,
+                best: entry.best,
+            }
+            .publish(&env);
+        }
+
+        persist(&env, &key, &entry);
+        roll
+    }
+
+    /**
+     * Pays out a closed contest's prize pool to the top of its leaderboard.
+     * Anyone may call this once the contest has closed.
+     *
+     * # Panics
+     *
+     * - If the contest is still open, or has already been paid.
+     */
+    pub fn close_contest(env: Env, id: u64) -> Contest {
+        Self::check_init(&env);
+        let mut contest = get(&env, id);
+        if env.ledger().sequence() <= contest.closes {
+            panic_with_error!(&env, Error::ContestOpen);
+        }
+        if contest.paid {
+            panic_with_error!(&env, Error::ContestClosed);
+        }
+
+        let contract = env.current_contract_address();
+        let client = token::Client::new(&env, &Self::token(&env));
+
+        contest.paid = true;
+        persist(&env, &ContestData::Contest(id), &contest);
+
+        // Nobody finished an entry, so the pool has no one to go to.
+        if contest.leaders.is_empty() {
+            if contest.pool > 0 {
+                let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+                client.transfer(&contract, &admin, &contest.pool);
+            }
+            return contest;
+        }
+
+        let shares: u32 = contest
+            .payouts
+            .iter()
+            .take(contest.leaders.len() as usize)
+            .sum();
+        let mut left = contest.pool;
+        for (place, standing) in contest.leaders.iter().enumerate().rev() {
+            // Rounding leaves the first place whatever's left over.
+            let prize = if place == 0 {
+                left
+            } else {
+                contest.pool * contest.payouts.get(place as u32).unwrap() as i128 / shares as i128
+            };
+            left -= prize;
+
+            if prize > 0 {
+                client.transfer(&contract, &standing.player, &prize);
+            }
+            ContestPrizeEvent {
+                id,
+                player: standing.player,
+                place: place as u32 + 1,
+                prize,
+            }
+            .publish(&env);
+        }
+        contest
+    }
+
+    /** Returns a contest, including its leaderboard. */
+    pub fn contest(env: Env, id: u64) -> Contest {
+        get(&env, id)
+    }
+
+    /** Returns a player's entry in a contest, if they've entered it. */
+    pub fn contest_entry(env: Env, id: u64, player: Address) -> Option<ContestEntry> {
+        env.storage()
+            .persistent()
+            .get(&ContestData::Entry(id, player))
+    }
+}
+
+fn get(env: &Env, id: u64) -> Contest {
+    match env.storage().persistent().get(&ContestData::Contest(id)) {
+        Some(contest) => contest,
+        None => panic_with_error!(env, Error::WrongMatch),
+    }
+}
+
+/** Returns the contest, panicking if it's closed. */
+fn open(env: &Env, id: u64) -> Contest {
+    let contest = get(env, id);
+    if env.ledger().sequence() > contest.closes {
+        panic_with_error!(env, Error::ContestClosed);
+    }
+    contest
+}
+
+/**
+ * Moves `player` up a best-first leaderboard of `places` to their new best
+ * `score`.
+ */
+pub fn rank(leaders: &mut Vec<Standing>, places: u32, player: &Address, score: u32) {
+    if let Some(i) = leaders.iter().position(|s| s.player == *player) {
+        leaders.remove(i as u32);
+    }
+
+    // Ties go to whoever got there first.
+    let at = leaders
+        .iter()
+        .position(|s| s.score < score)
+        .unwrap_or(leaders.len() as usize) as u32;
+    if at >= places {
+        return;
+    }
+
+    leaders.insert(
+        at,
+        Standing {
+            player: player.clone(),
+            score,
+        },
+    );
+    if leaders.len() > places {
+        leaders.pop_back();
+    }
+}
@@ -0,0 +1,188 @@
+//! A backgammon-style doubling cube.
+//!
+//! At the start of their turn, a player who holds the cube (or anyone, while
+//! it's still in the middle) may offer to double the stakes. Their opponent
+//! must accept, taking the cube and matching the extra wager, or decline and
+//! lose the match at the current stakes.
+
+use soroban_sdk::{contractevent, contractimpl, contracttype, panic_with_error};
+use soroban_sdk::{Address, Env, Vec};
+
+use crate::{house, Error, Farkle, FarkleArgs, FarkleClient, Outcome, UserData};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Cube {
+    pub value: u32,               // how many times the original stakes are up
+    pub owner: Option<Address>,   // who may double next, or none for either
+    pub offered: Option<Address>, // who is offering to double right now
+}
+
+#[contractevent(topics = ["double"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DoubleEvent {
+    #[topic]
+    player: Address,
+
+    value: u32,
+    accepted: bool, // false for an offer, true once it's taken
+}
+
+#[contractimpl]
+impl Farkle {
+    /**
+     * Offers the opponent to double the stakes of the match.
+     *
+     * This can only be done at the very start of your turn, and only if you
+     * hold the cube or nobody has doubled yet. Until the opponent answers
+     * with `accept_double` or `decline_double`, you can't `roll`.
+     *
+     * # Panics
+     *
+     * - If this `player` isn't in a match, or it isn't their turn.
+     * - If they've already rolled this turn.
+     * - If the opponent holds the cube, or the match isn't wagered.
+     * - If the opponent is the house, which never doubles.
+     * - If they can't afford to match the doubled stakes (from their wallet
+     *   and allowance, in a match begun with `engage_from`).
+     */
+    pub fn offer_double(env: Env, player: Address) {
+        Self::check_init(&env);
+        let store = env.storage().temporary();
+        let opp = Self::get_opp(&env, player.clone());
+
+        let turn: Address = store.get(&UserData::Turn(player.clone())).unwrap();
+        let dice: Vec<u32> = store
+            .get(&UserData::Dice(player.clone()))
+            .unwrap_or(Vec::new(&env));
+        if turn != player || !dice.is_empty() {
+            panic_with_error!(&env, Error::NotYourTurn);
+        }
+
+        let mut cube = get(&env, &player);
+        if cube.offered.is_some() {
+            panic_with_error!(&env, Error::DoublePending);
+        }
+        if cube.owner.as_ref().is_some_and(|owner| *owner != player) {
+            panic_with_error!(&env, Error::CantDouble);
+        }
+
+        let stake: i128 = store.get(&UserData::Stake(player.clone())).unwrap_or(0);
+        if stake == 0 || house::is(&env, &opp) {
+            panic_with_error!(&env, Error::CantDouble);
+        }
+        if !Self::can_raise(&env, &player, stake) {
+            panic_with_error!(&env, Error::TooPoor);
+        }
+
+        player.require_auth();
+        Self::bump_match_ttl(&env, player.clone(), opp.clone());
+
+        // The opponent gets a full turn's worth of time to answer.
+        store.set(&UserData::LastPlayed(opp.clone()), &env.ledger().sequence());
+
+        cube.offered = Some(player.clone());
+        set(&env, &player, &opp, &cube);
+
+        DoubleEvent {
+            player,
+            value: cube.value * 2,
+            accepted: false,
+        }
+        .publish(&env);
+    }
+
+    /**
+     * Accepts the opponent's offer to double, matching their extra wager and
+     * taking the cube.
+     *
+     * # Panics
+     *
+     * - If the opponent hasn't offered to double.
+     * - If either player can no longer afford the doubled stakes.
+     */
+    pub fn accept_double(env: Env, player: Address) {
+        Self::check_init(&env);
+        let opp = Self::get_opp(&env, player.clone());
+
+        let mut cube = get(&env, &player);
+        if cube.offered.as_ref() != Some(&opp) {
+            panic_with_error!(&env, Error::CantDouble);
+        }
+
+        player.require_auth();
+        Self::bump_match_ttl(&env, player.clone(), opp.clone());
+
+        // Both sides put up as much again as they already have.
+        let store = env.storage().temporary();
+        for who in [&player, &opp] {
+            let key = UserData::Stake(who.clone());
+            let stake: i128 = store.get(&key).unwrap();
+            Self::raise_stake(&env, who, stake);
+            store.set(&key, &(stake * 2));
+        }
+
+        cube.value *= 2;
+        cube.owner = Some(player.clone());
+        cube.offered = None;
+        set(&env, &player, &opp, &cube);
+
+        DoubleEvent {
+            player,
+            value: cube.value,
+            accepted: true,
+        }
+        .publish(&env);
+    }
+
+    /**
+     * Turns down the opponent's offer to double, which concedes the match to
+     * them at the current stakes.
+     *
+     * # Panics
+     *
+     * - If the opponent hasn't offered to double.
+     */
+    pub fn decline_double(env: Env, player: Address) {
+        Self::check_init(&env);
+        let opp = Self::get_opp(&env, player.clone());
+
+        if get(&env, &player).offered.as_ref() != Some(&opp) {
+            panic_with_error!(&env, Error::CantDouble);
+        }
+
+        player.require_auth();
+        Self::settle(&env, &opp, &player, 0, Outcome::Concede);
+    }
+
+    /** Returns the state of the doubling cube in a player's match. */
+    pub fn cube(env: Env, player: Address) -> Cube {
+        get(&env, &player)
+    }
+}
+
+pub fn get(env: &Env, player: &Address) -> Cube {
+    env.storage()
+        .temporary()
+        .get(&UserData::Cube(player.clone()))
+        .unwrap_or(Cube {
+            value: 1,
+            owner: None,
+            offered: None,
+        })
+}
+
+/** Returns who has to act next in `player`'s match, given whose `turn` it is. */
+pub fn to_act(env: &Env, player: &Address, turn: Address) -> Address {
+    match get(env, player).offered {
+        // An offer to double waits on whoever it was made to.
+        Some(offerer) => Farkle::get_opp(env, offerer),
+        None => turn,
+    }
+}
+
+fn set(env: &Env, a: &Address, b: &Address, cube: &Cube) {
+    let store = env.storage().temporary();
+    store.set(&UserData::Cube(a.clone()), cube);
+    store.set(&UserData::Cube(b.clone()), cube);
+}
@@ -0,0 +1,260 @@
+//! A daily challenge where everyone plays the same dice.
+//!
+//! Each UTC day gets a seed, and the dice for a player's nth roll of the day
+//! are derived from it, so every participant faces the same sequence and only
+//! their decisions set them apart. A player gets one run of `DAILY_TURNS`
+//! turns per day, and their total goes on that day's leaderboard.
+//!
+//! The seed never touches the ledger while its day is being played. The admin
+//! commits to the SHA-256 hash of each seed ahead of its day, and deals the
+//! dice off-chain with `seeded_dice`. Players submit the moves of their run,
+//! just as they'd pass them to `roll`, and once the day is over and the seed
+//! has been revealed, anyone can have a run checked against it. Only checked
+//! runs make the leaderboard.
+
+use soroban_sdk::{contractevent, contractimpl, contracttype, panic_with_error};
+use soroban_sdk::{Address, Bytes, BytesN, Env, Vec};
+
+use crate::contest::{self, Standing};
+use crate::solo::{self, Run};
+use crate::{persist, AdminData, DailyData, Error, Farkle, FarkleArgs, FarkleClient};
+
+pub const DAILY_TURNS: u32 = 10;
+const BOARD_SIZE: u32 = 20;
+const MAX_MOVES: u32 = 20 * DAILY_TURNS;
+const DAY: u64 = 60 * 60 * 24; // seconds
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct DailyEntry {
+    pub moves: Vec<(Vec<u32>, bool)>, // the `save` and `stop` of each roll
+    pub run: Run,                     // the run the moves play out, once checked
+    pub checked: bool,
+}
+
+#[contractevent(topics = ["daily_score"], data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DailyScoreEvent {
+    #[topic]
+    day: u64,
+
+    #[topic]
+    player: Address,
+
+    score: u32,
+}
+
+#[contractimpl]
+impl Farkle {
+    /** Returns the number of today's challenge (days since the Unix epoch). */
+    pub fn today(env: Env) -> u64 {
+        today(&env)
+    }
+
+    /**
+     * Commits to the seed of an upcoming day's challenge, by its SHA-256
+     * hash. Only the admin may do this.
+     *
+     * # Panics
+     *
+     * - If `day` has already begun, or its seed is already committed to.
+     */
+    pub fn commit_daily(env: Env, day: u64, hash: BytesN<32>) {
+        Self::check_init(&env);
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        let key = DailyData::Commit(day);
+        if day <= today(&env) || env.storage().persistent().has(&key) {
+            panic_with_error!(&env, Error::ContestOpen);
+        }
+        persist(&env, &key, &hash);
+    }
+
+    /**
+     * Submits a player's run of today's challenge.
+     *
+     * Each move is the `save` and `stop` of one roll, exactly as they'd be
+     * passed to `roll`, with banking or busting moving straight on to the
+     * player's next turn. The run is only scored once `check_daily` has
+     * played the moves against the day's seed.
+     *
+     * # Panics
+     *
+     * - If no seed was committed to for today.
+     * - If the player has already submitted a run today.
+     * - If there are more than 200 moves.
+     */
+    pub fn submit_daily(env: Env, player: Address, moves: Vec<(Vec<u32>, bool)>) {
+        Self::check_init(&env);
+        let day = today(&env);
+
+        let store = env.storage().persistent();
+        if !store.has(&DailyData::Commit(day)) {
+            panic_with_error!(&env, Error::ContestClosed);
+        }
+        let key = DailyData::Entry(day, player.clone());
+        if store.has(&key) {
+            panic_with_error!(&env, Error::AlreadyPlaying);
+        }
+        if moves.len() > MAX_MOVES {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        player.require_auth();
+
+        let entry = DailyEntry {
+            moves,
+            run: Run::new(&env),
+            checked: false,
+        };
+        persist(&env, &key, &entry);
+    }
+
+    /**
+     * Reveals the seed of a day's challenge, once the day is over. Anyone
+     * holding the seed may do this, since it has to match the commitment.
+     *
+     * # Panics
+     *
+     * - If the day isn't over, or was never committed to.
+     * - If `seed` doesn't hash to the commitment.
+     */
+    pub fn reveal_daily(env: Env, day: u64, seed: BytesN<32>) {
+        Self::check_init(&env);
+        if day >= today(&env) {
+            panic_with_error!(&env, Error::ContestOpen);
+        }
+
+        let store = env.storage().persistent();
+        let hash: BytesN<32> = match store.get(&DailyData::Commit(day)) {
+            Some(hash) => hash,
+            None => panic_with_error!(&env, Error::ContestClosed),
+        };
+        if env.crypto().sha256(&Bytes::from(&seed)).to_bytes() != hash {
+            panic_with_error!(&env, Error::BadSeed);
+        }
+        persist(&env, &DailyData::Seed(day), &seed);
+    }
+
+    /**
+     * Plays a submitted run against its day's revealed seed, and puts its
+     * score on the leaderboard. Anyone may call this.
+     *
+     * # Returns
+     *
+     * The run's score.
+     *
+     * # Panics
+     *
+     * - If the day's seed hasn't been revealed yet.
+     * - If the player didn't submit a run, or it's already been checked.
+     * - If a move is one `roll` would reject, or the moves don't play out
+     *   exactly `DAILY_TURNS` turns.
+     */
+    pub fn check_daily(env: Env, day: u64, player: Address) -> u32 {
+        Self::check_init(&env);
+
+        let store = env.storage().persistent();
+        let seed: BytesN<32> = match store.get(&DailyData::Seed(day)) {
+            Some(seed) => seed,
+            None => panic_with_error!(&env, Error::ContestOpen),
+        };
+        let key = DailyData::Entry(day, player.clone());
+        let mut entry: DailyEntry = match store.get(&key) {
+            Some(entry) => entry,
+            None => panic_with_error!(&env, Error::WrongMatch),
+        };
+        if entry.checked {
+            panic_with_error!(&env, Error::WrongMatch);
+        }
+
+        let mut index = 0;
+        for (save, stop) in entry.moves.iter() {
+            if entry.run.turns >= DAILY_TURNS {
+                panic_with_error!(&env, Error::WrongMatch);
+            }
+            solo::step(&env, &mut entry.run, save, stop, |count| {
+                index += 1;
+                seeded_dice(&env, &seed, index - 1, count)
+            });
+        }
+        if entry.run.turns < DAILY_TURNS {
+            panic_with_error!(&env, Error::WrongMatch);
+        }
+        entry.checked = true;
+        persist(&env, &key, &entry);
+
+        let board_key = DailyData::Board(day);
+        let mut board: Vec<Standing> = store.get(&board_key).unwrap_or(Vec::new(&env));
+        contest::rank(&mut board, BOARD_SIZE, &player, entry.run.score);
+        persist(&env, &board_key, &board);
+
+        DailyScoreEvent {
+            day,
+            player,
+            score: entry.run.score,
+        }
+        .publish(&env);
+
+        entry.run.score
+    }
+
+    /** Returns the best checked runs of a day's challenge, best first. */
+    pub fn daily_board(env: Env, day: u64) -> Vec<Standing> {
+        env.storage()
+            .persistent()
+            .get(&DailyData::Board(day))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /** Returns a player's run of a day's challenge, if they've submitted one. */
+    pub fn daily_entry(env: Env, day: u64, player: Address) -> Option<DailyEntry> {
+        env.storage()
+            .persistent()
+            .get(&DailyData::Entry(day, player))
+    }
+
+    /** Returns the seed of a day's challenge, once it's been revealed. */
+    pub fn daily_seed(env: Env, day: u64) -> Option<BytesN<32>> {
+        env.storage().persistent().get(&DailyData::Seed(day))
+    }
+
+    /**
+     * Returns the dice for roll number `index` (counting from zero) of a
+     * challenge with `seed`, when `count` dice are rolled.
+     *
+     * These are the first `count` dice drawn from the SHA-256 hash of the
+     * seed followed by the big-endian `index`, taking each byte below 252 as
+     * the die `byte % 6 + 1` and skipping the rest. If a hash runs out of
+     * bytes, the next one is the hash of the hash.
+     */
+    pub fn seeded_dice(env: Env, seed: BytesN<32>, index: u32, count: u32) -> Vec<u32> {
+        seeded_dice(&env, &seed, index, count)
+    }
+}
+
+fn today(env: &Env) -> u64 {
+    env.ledger().timestamp() / DAY
+}
+
+fn seeded_dice(env: &Env, seed: &BytesN<32>, index: u32, count: u32) -> Vec<u32> {
+    let mut data = Bytes::from(seed);
+    data.extend_from_array(&index.to_be_bytes());
+    let mut hash = env.crypto().sha256(&data).to_bytes();
+
+    let mut dice = Vec::new(env);
+    loop {
+        for byte in hash.iter() {
+            if dice.len() == count {
+                return dice;
+            }
+            // Skipping the top few values keeps every face equally likely.
+            if byte < 252 {
+                dice.push_back(byte as u32 % 6 + 1);
+            }
+        }
+        hash = env.crypto().sha256(&Bytes::from(hash)).to_bytes();
+    }
+}
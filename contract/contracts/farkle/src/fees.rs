@@ -0,0 +1,303 @@
+//! The fee vault.
+//!
+//! Fees are accrued in an internal ledger as they're charged, rather than
+//! sent to the admin one transfer at a time, and stay in the contract until
+//! the admin collects them. Collecting to any address (and in any amounts)
+//! lets the fees go to a treasury, and the admin can also set shares for
+//! several recipients to split them between.
+//!
+//! When the fee on a match's stakes is charged depends on the fee policy the
+//! match began under. Entry fees for contests, tournaments and leagues are
+//! always charged on entry.
+
+use soroban_sdk::{contractevent, contractimpl, contracttype, panic_with_error, token};
+use soroban_sdk::{Address, Env, Vec};
+
+use crate::{
+    jackpot, referral, staking, AdminData, Error, Farkle, FarkleArgs, FarkleClient, UserData,
+};
+
+const MAX_RECIPIENTS: u32 = 10;
+
+/** When the fee on a match's stakes is charged. */
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum FeePolicy {
+    OnEngage, // as the stakes are held, whatever becomes of the match
+    OnSettle, // only once the match has a winner, and never if it's aborted
+}
+
+/** The fee on one player's stake in a match. */
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct MatchFee {
+    pub policy: FeePolicy, // the policy the match began under
+    pub amount: i128,      // charged already, or owed at settlement
+}
+
+/** One recipient's share of the fees split by `split_fees`. */
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct FeeShare {
+    pub to: Address,
+    pub share: u32, // per mille
+}
+
+#[contractevent(topics = ["fees_collected"], data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeesCollectedEvent {
+    #[topic]
+    to: Address,
+
+    amount: i128,
+}
+
+#[contractimpl]
+impl Farkle {
+    /**
+     * Pays out accrued fees.
+     *
+     * # Arguments
+     *
+     * - `to` - Where the fees go.
+     * - `amount` - How much to pay, up to what's accrued.
+     *
+     * # Returns
+     *
+     * The fees left accrued afterwards.
+     */
+    pub fn collect_fees(env: Env, to: Address, amount: i128) -> i128 {
+        Self::check_init(&env);
+
+        let accrued = accrued(&env);
+        if amount <= 0 || amount > accrued {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        pay(&env, &to, amount);
+
+        let left = accrued - amount;
+        env.storage().instance().set(&AdminData::Fees, &left);
+        left
+    }
+
+    /**
+     * Pays out accrued fees between the recipients of the fee split, by
+     * their shares. Rounding leaves the first recipient whatever's left over.
+     *
+     * # Returns
+     *
+     * The fees left accrued afterwards.
+     *
+     * # Panics
+     *
+     * - If `amount` isn't positive, or is more than what's accrued.
+     * - If no fee split has been set.
+     */
+    pub fn split_fees(env: Env, amount: i128) -> i128 {
+        Self::check_init(&env);
+
+        let accrued = accrued(&env);
+        let split = split(&env);
+        if amount <= 0 || amount > accrued || split.is_empty() {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        let mut left = amount;
+        for (i, share) in split.iter().enumerate().rev() {
+            let paid = if i == 0 {
+                left
+            } else {
+                amount * share.share as i128 / 1000
+            };
+            left -= paid;
+            if paid > 0 {
+                pay(&env, &share.to, paid);
+            }
+        }
+
+        let left = accrued - amount;
+        env.storage().instance().set(&AdminData::Fees, &left);
+        left
+    }
+
+    /**
+     * Sets the recipients `split_fees` pays, with their shares per mille.
+     * Only the admin may do this.
+     *
+     * # Panics
+     *
+     * - If there are no recipients, or more than 10.
+     * - If a share is zero, or the shares don't add up to 1000.
+     */
+    pub fn set_fee_split(env: Env, split: Vec<FeeShare>) {
+        Self::check_init(&env);
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        if split.is_empty()
+            || split.len() > MAX_RECIPIENTS
+            || split.iter().any(|s| s.share == 0)
+            || split.iter().map(|s| s.share as u64).sum::<u64>() != 1000
+        {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+        env.storage().instance().set(&AdminData::FeeSplit, &split);
+    }
+
+    /** Returns the recipients of the fee split, and their shares. */
+    pub fn fee_split(env: Env) -> Vec<FeeShare> {
+        split(&env)
+    }
+
+    /** Returns the fees charged so far that haven't been collected. */
+    pub fn fees_accrued(env: Env) -> i128 {
+        accrued(&env)
+    }
+
+    /**
+     * Sets when the fee on the stakes of matches begun from now on is
+     * charged. Matches already underway keep the policy they began under.
+     *
+     * Under `FeePolicy::OnSettle`, a match the admin ends without a winner
+     * returns each player's full stake; under `FeePolicy::OnEngage`, it
+     * returns their stakes less the fee already charged.
+     */
+    pub fn set_fee_policy(env: Env, policy: FeePolicy) {
+        Self::check_init(&env);
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        env.storage().instance().set(&AdminData::FeePolicy, &policy);
+    }
+
+    /** Returns the fee policy new matches begin under. */
+    pub fn fee_policy(env: Env) -> FeePolicy {
+        policy(&env)
+    }
+}
+
+pub fn policy(env: &Env) -> FeePolicy {
+    env.storage()
+        .instance()
+        .get(&AdminData::FeePolicy)
+        .unwrap_or(FeePolicy::OnEngage)
+}
+
+/**
+ * Takes the fee on `amount` more of `player`'s stake in their match, under
+ * the policy the match began with (or the current one, for a new match).
+ */
+pub fn hold(env: &Env, player: &Address, amount: i128) {
+    let store = env.storage().temporary();
+    let key = UserData::Fee(player.clone());
+    let mut fee = store.get(&key).unwrap_or(MatchFee {
+        policy: policy(env),
+        amount: 0,
+    });
+
+    let charge = Farkle::get_fee(amount);
+    if fee.policy == FeePolicy::OnEngage {
+        accrue_match(env, player, charge);
+    }
+    fee.amount += charge;
+    store.set(&key, &fee);
+}
+
+/** Returns the fee on `player`'s stake in their match, charged or not. */
+pub fn on_stake(env: &Env, player: &Address) -> i128 {
+    get(env, player).map_or(0, |fee| fee.amount)
+}
+
+/** Returns the fee already charged on `player`'s stake in their match. */
+pub fn charged(env: &Env, player: &Address) -> i128 {
+    match get(env, player) {
+        Some(fee) if fee.policy == FeePolicy::OnEngage => fee.amount,
+        _ => 0,
+    }
+}
+
+/** Charges whatever fees a match that's been won still owes. */
+pub fn settle(env: &Env, winner: &Address, loser: &Address) {
+    for player in [winner, loser] {
+        if let Some(fee) = get(env, player) {
+            if fee.policy == FeePolicy::OnSettle {
+                accrue_match(env, player, fee.amount);
+            }
+        }
+    }
+}
+
+fn get(env: &Env, player: &Address) -> Option<MatchFee> {
+    env.storage()
+        .temporary()
+        .get(&UserData::Fee(player.clone()))
+}
+
+/** Adds a charged fee to the vault. */
+pub fn accrue(env: &Env, fee: i128) {
+    if fee > 0 {
+        let total = accrued(env) + fee;
+        env.storage().instance().set(&AdminData::Fees, &total);
+    }
+}
+
+/**
+ * Adds the fee on `player`'s stake in a match to the vault, less the shares
+ * of the jackpot, the player's referrer and stakers.
+ */
+pub fn accrue_match(env: &Env, player: &Address, fee: i128) {
+    let paid = referral::pay(env, player, fee) + staking::fund(env, fee);
+    accrue(env, jackpot::fund(env, fee) - paid);
+}
+
+/**
+ * Adds the fee on an entry fee `player` paid to the vault, less their
+ * referrer's share. Entry fees go into a prize pool rather than a pot, so the
+ * jackpot and stakers only share in match fees.
+ */
+pub fn accrue_entry(env: &Env, player: &Address, fee: i128) {
+    accrue(env, fee - referral::pay(env, player, fee));
+}
+
+/**
+ * Panics unless the shares (per mille) of each match fee paid out to the
+ * jackpot, referrers and stakers leave something for the vault.
+ */
+pub fn check_shares(env: &Env, jackpot: u32, referral: u32, staking: u32) {
+    if jackpot as u64 + referral as u64 + staking as u64 > 1000 {
+        panic_with_error!(env, Error::InvalidAmount);
+    }
+}
+
+fn split(env: &Env) -> Vec<FeeShare> {
+    env.storage()
+        .instance()
+        .get(&AdminData::FeeSplit)
+        .unwrap_or(Vec::new(env))
+}
+
+/** Sends `amount` of the vault to `to`. */
+fn pay(env: &Env, to: &Address, amount: i128) {
+    let contract = env.current_contract_address();
+    let client = token::Client::new(env, &Farkle::token(env));
+    client.transfer(&contract, to, &amount);
+
+    FeesCollectedEvent {
+        to: to.clone(),
+        amount,
+    }
+    .publish(env);
+}
+
+fn accrued(env: &Env) -> i128 {
+    env.storage().instance().get(&AdminData::Fees).unwrap_or(0)
+}
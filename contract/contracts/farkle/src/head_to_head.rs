@@ -0,0 +1,86 @@
+//! Head-to-head records between pairs of players.
+//!
+//! Each pair is stored once, under its addresses in sorted order, and flipped
+//! to match the caller's point of view when read back.
+
+use soroban_sdk::{contracttype, Address, Env};
+
+use crate::{persist, Outcome, UserData};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct HeadToHead {
+    pub a: Address,
+    pub b: Address,
+    pub a_wins: u32,
+    pub b_wins: u32,
+    pub played: u32, // includes matches ended without a winner
+
+    // The rest describes the last match, so only means something once
+    // `played` is non-zero.
+    pub last_winner: Option<Address>, // none if the admin ended the match
+    pub last_outcome: Outcome,
+    pub last_ledger: u32, // ledger the match ended on
+}
+
+/** Returns the record between `a` and `b`, from `a`'s point of view. */
+pub fn get(env: &Env, a: &Address, b: &Address) -> HeadToHead {
+    let (lo, hi) = sorted(a, b);
+    let record: HeadToHead = env
+        .storage()
+        .persistent()
+        .get(&UserData::HeadToHead(lo.clone(), hi.clone()))
+        .unwrap_or(HeadToHead {
+            a: lo.clone(),
+            b: hi.clone(),
+            a_wins: 0,
+            b_wins: 0,
+            played: 0,
+            last_winner: None,
+            last_outcome: Outcome::Aborted,
+            last_ledger: 0,
+        });
+
+    if record.a == *a {
+        return record;
+    }
+
+    HeadToHead {
+        a: record.b,
+        b: record.a,
+        a_wins: record.b_wins,
+        b_wins: record.a_wins,
+        ..record
+    }
+}
+
+/**
+ * Records the end of a match between `a` and `b`.
+ *
+ * `winner` should be one of the two, or none if nobody won the match.
+ */
+pub fn record(env: &Env, a: &Address, b: &Address, winner: Option<Address>, outcome: Outcome) {
+    let (lo, hi) = sorted(a, b);
+    let mut record = get(env, lo, hi);
+
+    if winner.as_ref() == Some(lo) {
+        record.a_wins += 1;
+    } else if winner.as_ref() == Some(hi) {
+        record.b_wins += 1;
+    }
+    record.played += 1;
+    record.last_winner = winner;
+    record.last_outcome = outcome;
+    record.last_ledger = env.ledger().sequence();
+
+    let key = UserData::HeadToHead(lo.clone(), hi.clone());
+    persist(env, &key, &record);
+}
+
+pub fn sorted<'a>(a: &'a Address, b: &'a Address) -> (&'a Address, &'a Address) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
@@ -0,0 +1,126 @@
+//! A compact, permanent record of every match that ends.
+//!
+//! Results are numbered in the order they finish. Each player also keeps an
+//! index into those numbers, so both views can be paged from newest to oldest.
+
+use soroban_sdk::{contracttype, Address, Env, Vec};
+
+use crate::{persist, Farkle, HistoryData, Outcome, UserData};
+
+const MAX_PAGE: u32 = 20;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct MatchResult {
+    pub id: u64,
+    pub a: Address,
+    pub b: Address,
+    pub stake_a: i128,
+    pub stake_b: i128,
+    pub score_a: u32,
+    pub score_b: u32,
+    pub winner: Option<Address>, // none if the admin ended the match
+    pub outcome: Outcome,
+    pub start_ledger: u32,
+    pub end_ledger: u32,
+}
+
+/**
+ * Records the end of the match between `a` and `b`, which must still be in
+ * progress (i.e. before its temporary state is removed).
+ *
+ * `score` is a floor for the winner's final score, since a winning roll is
+ * never banked into their stored score.
+ */
+pub fn record(
+    env: &Env,
+    a: &Address,
+    b: &Address,
+    winner: Option<Address>,
+    outcome: Outcome,
+    score: u32,
+) {
+    let temp = env.storage().temporary();
+    let final_score = |player: &Address, opp: &Address| -> u32 {
+        let banked = Farkle::banked(env, player, opp);
+        if winner.as_ref() == Some(player) {
+            score.max(banked)
+        } else {
+            banked
+        }
+    };
+
+    let instance = env.storage().instance();
+    let id: u64 = instance.get(&HistoryData::Count).unwrap_or(0);
+    instance.set(&HistoryData::Count, &(id + 1));
+
+    let result = MatchResult {
+        id,
+        a: a.clone(),
+        b: b.clone(),
+        stake_a: Farkle::held(env, a, b),
+        stake_b: Farkle::held(env, b, a),
+        score_a: final_score(a, b),
+        score_b: final_score(b, a),
+        winner,
+        outcome,
+        // The house doesn't keep its own copy of when the match began.
+        start_ledger: temp
+            .get(&UserData::Started(a.clone()))
+            .or_else(|| temp.get(&UserData::Started(b.clone())))
+            .unwrap_or(0),
+        end_ledger: env.ledger().sequence(),
+    };
+    persist(env, &HistoryData::Result(id), &result);
+
+    for player in [a, b] {
+        let key = HistoryData::PlayerCount(player.clone());
+        let n: u32 = env.storage().persistent().get(&key).unwrap_or(0);
+        persist(env, &HistoryData::PlayerMatch(player.clone(), n), &id);
+        persist(env, &key, &(n + 1));
+    }
+}
+
+/** Returns up to `limit` results involving `player`, skipping the newest `cursor`. */
+pub fn for_player(env: &Env, player: &Address, cursor: u32, limit: u32) -> Vec<MatchResult> {
+    let store = env.storage().persistent();
+    let count: u32 = store
+        .get(&HistoryData::PlayerCount(player.clone()))
+        .unwrap_or(0);
+
+    let mut page = Vec::new(env);
+    for i in window(count as u64, cursor, limit) {
+        let id: u64 = store
+            .get(&HistoryData::PlayerMatch(player.clone(), i as u32))
+            .unwrap();
+        page.push_back(store.get(&HistoryData::Result(id)).unwrap());
+    }
+    page
+}
+
+/** Returns up to `limit` results from every player, skipping the newest `cursor`. */
+pub fn recent(env: &Env, cursor: u32, limit: u32) -> Vec<MatchResult> {
+    let count: u64 = env
+        .storage()
+        .instance()
+        .get(&HistoryData::Count)
+        .unwrap_or(0);
+
+    let mut page = Vec::new(env);
+    for id in window(count, cursor, limit) {
+        page.push_back(
+            env.storage()
+                .persistent()
+                .get(&HistoryData::Result(id))
+                .unwrap(),
+        );
+    }
+    page
+}
+
+/** Indices for a newest-first page over `count` entries. */
+fn window(count: u64, cursor: u32, limit: u32) -> impl Iterator<Item = u64> {
+    let end = count.saturating_sub(cursor as u64);
+    let start = end.saturating_sub(limit.min(MAX_PAGE) as u64);
+    (start..end).rev()
+}
@@ -0,0 +1,280 @@
+//! Solo play against the house.
+//!
+//! The house takes the opponent's seat under the contract's own address, and
+//! matches the player's stake out of a bankroll the admin funds. Its turns
+//! are played inside the player's own `roll` (or `engage_house`) call, so no
+//! second signer is ever needed.
+//!
+//! The house always plays the same, published strategy: it keeps every
+//! scoring die, and banks once its turn is worth `BANK_AT` points, once it
+//! has fewer than `MIN_DICE` dice left to roll, or as soon as banking would
+//! win the match.
+//!
+//! Since the contract address is shared by every house match, none of the
+//! house's state is kept under it. Its score and stake are kept alongside
+//! the player's instead.
+
+use soroban_sdk::{contractimpl, contracttype, panic_with_error, token};
+use soroban_sdk::{Address, Env, Vec};
+
+use crate::WIN_THRESHOLD;
+use crate::{AdminData, Error, Farkle, FarkleArgs, FarkleClient, MatchTerms, Outcome, UserData};
+
+pub const BANK_AT: u32 = 300;
+pub const MIN_DICE: u32 = 3;
+
+// Caps how long a single house turn can run on a lucky streak of hot dice.
+const MAX_ROLLS: u32 = 12;
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct Bankroll {
+    pub balance: i128,  // funds the house owns, including what's at stake
+    pub exposure: i128, // funds the house has at stake in open matches
+    pub limit: i128,    // the most the house may have at stake at once
+}
+
+#[contractimpl]
+impl Farkle {
+    /**
+     * Begins a match against the house.
+     *
+     * The house matches `stake` out of its bankroll and then plays its turns
+     * automatically, so only the player ever has to call `roll`.
+     *
+     * # Returns
+     *
+     * The address of the player who should go first: the player, or the
+     * contract itself if the house went first (and has already played).
+     *
+     * # Panics
+     *
+     * - If `stake` is less than `COST_TO_PLAY`.
+     * - If the player is already in a game, or doesn't have `stake` deposited.
+     * - If matching `stake` would take the house past its limits.
+     */
+    pub fn engage_house(env: Env, player: Address, stake: i128) -> Address {
+        Self::check_init(&env);
+        Self::check_stake(&env, stake);
+        Self::check_free(&env, &player);
+
+        let mut bank = bankroll(&env);
+        bank.exposure += stake;
+        if bank.exposure > bank.limit || bank.exposure > bank.balance {
+            panic_with_error!(&env, Error::HouseLimit);
+        }
+
+        player.require_auth();
+        Self::hold_stake(&env, &player, stake);
+        set_bankroll(&env, &bank);
+
+        let store = env.storage().temporary();
+        store.set(&UserData::HouseStake(player.clone()), &stake);
+        store.set(&UserData::HouseScore(player.clone()), &0u32);
+
+        let house = env.current_contract_address();
+        let first: u64 = env.prng().gen_range(1..=2);
+        let first = Self::start_match(&env, &player, &house, first == 1, &MatchTerms::even(stake));
+        if first == house {
+            play(&env, &player);
+        }
+
+        first
+    }
+
+    /** Adds funds from the admin to the house's bankroll. */
+    pub fn fund_house(env: Env, amount: i128) -> Bankroll {
+        Self::check_init(&env);
+        if amount <= 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        let contract = env.current_contract_address();
+        let client = token::Client::new(&env, &Self::token(&env));
+        client.transfer(&admin, &contract, &amount);
+
+        let mut bank = bankroll(&env);
+        bank.balance += amount;
+        set_bankroll(&env, &bank);
+        bank
+    }
+
+    /** Returns funds the house doesn't have at stake to the admin. */
+    pub fn drain_house(env: Env, amount: i128) -> Bankroll {
+        Self::check_init(&env);
+
+        let mut bank = bankroll(&env);
+        if amount <= 0 || amount > bank.balance - bank.exposure {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        let contract = env.current_contract_address();
+        let client = token::Client::new(&env, &Self::token(&env));
+        client.transfer(&contract, &admin, &amount);
+
+        bank.balance -= amount;
+        set_bankroll(&env, &bank);
+        bank
+    }
+
+    /** Sets the most the house may have at stake across all its matches. */
+    pub fn set_house_limit(env: Env, limit: i128) -> Bankroll {
+        Self::check_init(&env);
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        let mut bank = bankroll(&env);
+        bank.limit = limit;
+        set_bankroll(&env, &bank);
+        bank
+    }
+
+    /** Returns the state of the house's bankroll. */
+    pub fn bankroll(env: Env) -> Bankroll {
+        bankroll(&env)
+    }
+
+    /** Returns the house's current score in its match against `player`. */
+    pub fn house_score(env: Env, player: Address) -> u32 {
+        score(&env, &player)
+    }
+}
+
+/** Whether `player` is the house's seat. */
+pub fn is(env: &Env, player: &Address) -> bool {
+    *player == env.current_contract_address()
+}
+
+/** Returns what the house has at stake against `player`. */
+pub fn stake(env: &Env, player: &Address) -> i128 {
+    env.storage()
+        .temporary()
+        .get(&UserData::HouseStake(player.clone()))
+        .unwrap_or(0)
+}
+
+/** Returns the house's score against `player`. */
+pub fn score(env: &Env, player: &Address) -> u32 {
+    env.storage()
+        .temporary()
+        .get(&UserData::HouseScore(player.clone()))
+        .unwrap_or(0)
+}
+
+/**
+ * Closes the house's side of its match against `player`, moving `won` (or,
+ * if negative, what it lost) into the bankroll.
+ */
+pub fn release(env: &Env, player: &Address, won: i128) {
+    let mut bank = bankroll(env);
+    bank.exposure -= stake(env, player);
+    bank.balance += won;
+    set_bankroll(env, &bank);
+
+    let store = env.storage().temporary();
+    for key in [
+        UserData::HouseStake(player.clone()),
+        UserData::HouseScore(player.clone()),
+    ] {
+        if store.has(&key) {
+            store.remove(&key);
+        }
+    }
+}
+
+/** Plays the house's turn against `player`, then hands the turn back. */
+pub fn play(env: &Env, player: &Address) {
+    let house = env.current_contract_address();
+    let store = env.storage().temporary();
+
+    let score = score(env, player);
+    let mut turn = 0;
+    let mut count = 6;
+
+    for _ in 0..MAX_ROLLS {
+        let roll = Farkle::roll_dice(env, count);
+        let points = Farkle::score_turn(env, &roll, false);
+        if points == 0 {
+            Farkle::emit_bust(env, house.clone(), &roll);
+            turn = 0;
+            break;
+        }
+
+        Farkle::emit_roll(env, house.clone(), &roll);
+        turn += points;
+        count -= scoring(&roll);
+        if count == 0 {
+            count = 6; // hot dice
+        }
+
+        if score + turn >= WIN_THRESHOLD || turn >= BANK_AT || count < MIN_DICE {
+            break;
+        }
+    }
+
+    if turn > 0 {
+        Farkle::emit_reroll(env, &house, &Vec::new(env), turn, true);
+    }
+
+    if score + turn >= WIN_THRESHOLD {
+        Farkle::send_rewards(env, &house, player);
+        Farkle::settle(env, &house, player, score + turn, Outcome::Win);
+        return;
+    }
+
+    store.set(&UserData::HouseScore(player.clone()), &(score + turn));
+    store.set(&UserData::Turn(player.clone()), player);
+    store.set(
+        &UserData::LastPlayed(player.clone()),
+        &env.ledger().sequence(),
+    );
+}
+
+/** How many dice in `roll` the house keeps, following `score_turn`'s rules. */
+fn scoring(roll: &Vec<u32>) -> u32 {
+    let mut counts = [0u32; 7];
+    for die in roll.iter() {
+        counts[die as usize] += 1;
+    }
+
+    // A full straight uses every die; a short one uses one of each of its five.
+    if (1..=6).all(|i| counts[i] > 0) {
+        return 6;
+    }
+
+    let mut kept = 0;
+    for straight in [1..=5, 2..=6] {
+        if straight.clone().all(|i| counts[i] > 0) {
+            straight.for_each(|i| counts[i] -= 1);
+            kept += 5;
+            break;
+        }
+    }
+
+    for count in counts.iter_mut() {
+        if *count >= 3 {
+            kept += *count;
+            *count = 0;
+        }
+    }
+
+    kept + counts[1] + counts[5]
+}
+
+fn bankroll(env: &Env) -> Bankroll {
+    env.storage()
+        .instance()
+        .get(&AdminData::Bankroll)
+        .unwrap_or_default()
+}
+
+fn set_bankroll(env: &Env, bank: &Bankroll) {
+    env.storage().instance().set(&AdminData::Bankroll, bank);
+}
@@ -0,0 +1,175 @@
+//! A progressive jackpot for rare rolls.
+//!
+//! The admin sets aside a share of each match's fee for the jackpot, and a
+//! list of rolls that win it, such as six of a kind or a 1-6 straight. When
+//! a player in a wagered match rolls one of them, a share of the pool is
+//! added to their balance. Until the admin configures it, the jackpot takes
+//! nothing and pays nothing.
+
+use soroban_sdk::{contractevent, contractimpl, contracttype, panic_with_error};
+use soroban_sdk::{Address, Env, Vec};
+
+use crate::{fees, referral, series, staking, AdminData, Error, Farkle, FarkleArgs, FarkleClient};
+
+/** A roll that wins the jackpot. */
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum JackpotPattern {
+    OfAKind(u32), // at least this many of the same die
+    Straight,     // 1 2 3 4 5 6
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct JackpotTrigger {
+    pub pattern: JackpotPattern,
+    pub opening: bool, // whether it only counts on the first roll of a turn
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct JackpotConfig {
+    pub share: u32,  // per mille of each match fee paid into the pool
+    pub payout: u32, // per mille of the pool paid out on a win
+    pub triggers: Vec<JackpotTrigger>,
+}
+
+#[contractevent(topics = ["jackpot"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct JackpotEvent {
+    #[topic]
+    player: Address,
+
+    prize: i128,
+    roll: Vec<u32>,
+}
+
+#[contractimpl]
+impl Farkle {
+    /**
+     * Configures the jackpot. Changes apply to fees charged and rolls made
+     * from now on, and leave the pool as it is.
+     *
+     * # Panics
+     *
+     * - If the shares of match fees paid out come to more than 1000.
+     * - If the payout is zero or above 1000.
+     * - If a trigger asks for fewer than 4 or more than 6 of a kind.
+     */
+    pub fn set_jackpot(env: Env, config: JackpotConfig) {
+        Self::check_init(&env);
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        let kinds_ok = config.triggers.iter().all(|t| match t.pattern {
+            JackpotPattern::OfAKind(n) => (4..=6).contains(&n),
+            JackpotPattern::Straight => true,
+        });
+        fees::check_shares(
+            &env,
+            config.share,
+            referral::share(&env),
+            staking::share(&env),
+        );
+        if !(1..=1000).contains(&config.payout) || !kinds_ok {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        env.storage()
+            .instance()
+            .set(&AdminData::JackpotConfig, &config);
+    }
+
+    /** Returns how the jackpot is configured. */
+    pub fn jackpot_config(env: Env) -> JackpotConfig {
+        config(&env)
+    }
+
+    /** Returns the size of the jackpot pool. */
+    pub fn jackpot(env: Env) -> i128 {
+        pool(&env)
+    }
+}
+
+/** Pays the jackpot's share of a match `fee` into the pool, returning the rest. */
+pub fn fund(env: &Env, fee: i128) -> i128 {
+    let share = fee * config(env).share as i128 / 1000;
+    if share > 0 {
+        let total = pool(env) + share;
+        env.storage().instance().set(&AdminData::Jackpot, &total);
+    }
+    fee - share
+}
+
+/**
+ * Pays out the jackpot if `player`'s `roll` (the first of a turn, if
+ * `opening`) wins it. Only wagered matches are eligible.
+ */
+pub fn check(env: &Env, player: &Address, opp: &Address, roll: &Vec<u32>, opening: bool) {
+    let config = config(env);
+    let won = config
+        .triggers
+        .iter()
+        .any(|t| (opening || !t.opening) && matches(roll, &t.pattern));
+    if !won {
+        return;
+    }
+
+    if Farkle::held(env, player, opp) <= 0 && series::current(env, player).is_none() {
+        return;
+    }
+
+    let total = pool(env);
+    let prize = total * config.payout as i128 / 1000;
+    if prize <= 0 {
+        return;
+    }
+
+    env.storage()
+        .instance()
+        .set(&AdminData::Jackpot, &(total - prize));
+    Farkle::credit(env, player, prize);
+
+    JackpotEvent {
+        player: player.clone(),
+        prize,
+        roll: roll.clone(),
+    }
+    .publish(env);
+}
+
+fn matches(roll: &Vec<u32>, pattern: &JackpotPattern) -> bool {
+    let mut counts = [0u32; 7];
+    for die in roll.iter() {
+        counts[die as usize] += 1;
+    }
+
+    match pattern {
+        JackpotPattern::OfAKind(n) => counts.iter().any(|c| c >= n),
+        JackpotPattern::Straight => counts[1..].iter().all(|c| *c == 1),
+    }
+}
+
+/** Returns the share (per mille) of each match fee paid into the pool. */
+pub fn share(env: &Env) -> u32 {
+    config(env).share
+}
+
+fn config(env: &Env) -> JackpotConfig {
+    env.storage()
+        .instance()
+        .get(&AdminData::JackpotConfig)
+        .unwrap_or(JackpotConfig {
+            share: 0,
+            payout: 1000,
+            triggers: Vec::new(env),
+        })
+}
+
+fn pool(env: &Env) -> i128 {
+    env.storage()
+        .instance()
+        .get(&AdminData::Jackpot)
+        .unwrap_or(0)
+}
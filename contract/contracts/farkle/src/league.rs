@@ -0,0 +1,439 @@
+//! Round-robin league seasons.
+//!
+//! The admin opens a season for a fixed list of entrants, and the contract
+//! schedules every pairing between them. Entrants join by paying the entry
+//! fee, then play their fixtures (in any order) as ordinary matches through
+//! `roll`. Results are kept in standings, and when the season ends the pool
+//! of entry fees is paid out to the top of the table.
+
+use soroban_sdk::{contractevent, contractimpl, contracttype, panic_with_error, token};
+use soroban_sdk::{Address, Env, Vec};
+
+use crate::{
+    persist, AdminData, Error, Farkle, FarkleArgs, FarkleClient, LeagueData, MatchTerms, UserData,
+};
+
+const MAX_ENTRANTS: u32 = 16;
+const WIN_POINTS: u32 = 2;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Season {
+    pub id: u64,
+    pub fee: i128,
+    pub ends: u32,         // ledger after which fixtures no longer count
+    pub payouts: Vec<u32>, // per-mille share of the pool, by place
+    pub pool: i128,        // entry fees, less the usual fee
+    pub entrants: Vec<Address>,
+    pub joined: Vec<Address>, // entrants who have paid the fee
+    pub fixtures: Vec<Fixture>,
+    pub table: Vec<LeagueStanding>, // in the same order as `entrants`
+    pub closed: bool,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Fixture {
+    pub round: u32, // suggested order of play, counting from 1
+    pub a: Address,
+    pub b: Address,
+    pub live: bool, // whether it's being played right now
+    pub winner: Option<Address>,
+    pub a_score: u32,
+    pub b_score: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct LeagueStanding {
+    pub player: Address,
+    pub played: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub points: u32,
+    pub diff: i64, // points scored less points conceded, across all fixtures
+}
+
+#[contractevent(topics = ["fixture"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FixtureEvent {
+    #[topic]
+    id: u64,
+
+    #[topic]
+    winner: Address,
+
+    fixture: u32,
+    loser: Address,
+}
+
+#[contractimpl]
+impl Farkle {
+    /**
+     * Opens a league season, scheduling every pairing of `entrants`.
+     *
+     * # Arguments
+     *
+     * `entrants` - Who plays in the season, from 2 to 16 players.
+     * `fee` - What each entrant pays to join, from their balance.
+     * `ends` - The ledger after which the season is over.
+     * `payouts` - The share of the pool (per mille, adding up to 1000) for
+     *      each place in the final standings, from first down.
+     *
+     * # Returns
+     *
+     * The identifier of the season.
+     */
+    pub fn open_season(
+        env: Env,
+        entrants: Vec<Address>,
+        fee: i128,
+        ends: u32,
+        payouts: Vec<u32>,
+    ) -> u64 {
+        Self::check_init(&env);
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        let unique = entrants
+            .iter()
+            .enumerate()
+            .all(|(i, p)| !entrants.slice(..i as u32).contains(&p));
+        if !(2..=MAX_ENTRANTS).contains(&entrants.len())
+            || !unique
+            || fee < 0
+            || ends <= env.ledger().sequence()
+            || payouts.is_empty()
+            || payouts.len() > entrants.len()
+            || payouts.iter().sum::<u32>() != 1000
+        {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        let mut table = Vec::new(&env);
+        for player in entrants.iter() {
+            table.push_back(LeagueStanding {
+                player,
+                played: 0,
+                wins: 0,
+                losses: 0,
+                points: 0,
+                diff: 0,
+            });
+        }
+
+        let instance = env.storage().instance();
+        let id: u64 = instance.get(&LeagueData::Count).unwrap_or(0);
+        instance.set(&LeagueData::Count, &(id + 1));
+
+        let season = Season {
+            id,
+            fee,
+            ends,
+            payouts,
+            pool: 0,
+            fixtures: schedule(&env, &entrants),
+            entrants,
+            joined: Vec::new(&env),
+            table,
+            closed: false,
+        };
+        set(&env, &season);
+        id
+    }
+
+    /**
+     * Pays an entrant's fee for a season, so they can play their fixtures.
+     *
+     * # Panics
+     *
+     * - If the player isn't one of the season's entrants, or already joined.
+     * - If the season is over.
+     * - If they don't have the fee deposited.
+     */
+    pub fn join_season(env: Env, player: Address, id: u64) {
+        Self::check_init(&env);
+        let mut season = open(&env, id);
+        if !season.entrants.contains(&player) || season.joined.contains(&player) {
+            panic_with_error!(&env, Error::WrongMatch);
+        }
+
+        player.require_auth();
+        if season.fee > 0 {
+            Self::hold_balance(&env, &player, season.fee);
+            season.pool += season.fee - Self::get_fee(season.fee);
+        }
+
+        season.joined.push_back(player);
+        set(&env, &season);
+    }
+
+    /**
+     * Begins the match for one of a season's fixtures, between two players
+     * who have both joined. Like `engage`, both of them authorize it.
+     *
+     * # Returns
+     *
+     * The address of the player who should go first.
+     *
+     * # Panics
+     *
+     * - If the season is over.
+     * - If the fixture has already been (or is being) played.
+     * - If either player hasn't joined the season, or is already in a game.
+     */
+    pub fn play_fixture(env: Env, id: u64, fixture: u32) -> Address {
+        Self::check_init(&env);
+        let mut season = open(&env, id);
+
+        let mut f = match season.fixtures.get(fixture) {
+            Some(f) => f,
+            None => panic_with_error!(&env, Error::WrongMatch),
+        };
+        if f.live || f.winner.is_some() {
+            panic_with_error!(&env, Error::WrongMatch);
+        }
+        if !season.joined.contains(&f.a) || !season.joined.contains(&f.b) {
+            panic_with_error!(&env, Error::WrongMatch);
+        }
+
+        Self::check_free(&env, &f.a);
+        Self::check_free(&env, &f.b);
+        f.a.require_auth();
+        f.b.require_auth();
+
+        f.live = true;
+        season.fixtures.set(fixture, f.clone());
+        set(&env, &season);
+
+        let store = env.storage().temporary();
+        store.set(&UserData::Fixture(f.a.clone()), &(id, fixture));
+        store.set(&UserData::Fixture(f.b.clone()), &(id, fixture));
+
+        let first: u64 = env.prng().gen_range(1..=2);
+        Self::start_match(&env, &f.a, &f.b, first == 1, &MatchTerms::even(0))
+    }
+
+    /**
+     * Ends a season once its last ledger has passed, paying the pool out by
+     * the final standings. Anyone may call this.
+     *
+     * Places are decided by points, then point differential, then wins, and
+     * only entrants who joined the season are placed.
+     *
+     * # Panics
+     *
+     * - If the season isn't over yet, or has already been closed.
+     */
+    pub fn close_season(env: Env, id: u64) -> Vec<LeagueStanding> {
+        Self::check_init(&env);
+        let mut season = get(&env, id);
+        if env.ledger().sequence() <= season.ends {
+            panic_with_error!(&env, Error::SeasonInProgress);
+        }
+        if season.closed {
+            panic_with_error!(&env, Error::SeasonOver);
+        }
+
+        let mut places = Vec::new(&env);
+        for standing in standings(&env, &season).iter() {
+            if season.joined.contains(&standing.player) {
+                places.push_back(standing);
+            }
+        }
+
+        let contract = env.current_contract_address();
+        let client = token::Client::new(&env, &Self::token(&env));
+        let mut left = season.pool;
+        for (place, standing) in places.iter().enumerate().rev() {
+            let Some(share) = season.payouts.get(place as u32) else {
+                continue;
+            };
+
+            // Rounding (and any empty places) leaves the rest to first place.
+            let prize = if place == 0 {
+                left
+            } else {
+                season.pool * share as i128 / 1000
+            };
+            left -= prize;
+            if prize > 0 {
+                client.transfer(&contract, &standing.player, &prize);
+            }
+        }
+
+        season.closed = true;
+        set(&env, &season);
+        places
+    }
+
+    /** Returns a season, including its fixtures. */
+    pub fn season(env: Env, id: u64) -> Season {
+        get(&env, id)
+    }
+
+    /** Returns a season's standings, best first. */
+    pub fn season_standings(env: Env, id: u64) -> Vec<LeagueStanding> {
+        standings(&env, &get(&env, id))
+    }
+}
+
+/** Returns the season and fixture `player` is currently playing, if any. */
+pub fn current(env: &Env, player: &Address) -> Option<(u64, u32)> {
+    env.storage()
+        .temporary()
+        .get(&UserData::Fixture(player.clone()))
+}
+
+/**
+ * Records the result of a fixture, whose match has already been closed out,
+ * with the final `scores` of the winner and loser respectively.
+ */
+pub fn record_game(
+    env: &Env,
+    (id, fixture): (u64, u32),
+    winner: &Address,
+    loser: &Address,
+    scores: (u32, u32),
+) {
+    unlink(env, winner, loser);
+
+    let mut season = get(env, id);
+    let mut f = season.fixtures.get(fixture).unwrap();
+    f.live = false;
+
+    // Results after the season is over don't count.
+    if season.closed || env.ledger().sequence() > season.ends {
+        season.fixtures.set(fixture, f);
+        set(env, &season);
+        return;
+    }
+
+    f.winner = Some(winner.clone());
+    (f.a_score, f.b_score) = if f.a == *winner {
+        scores
+    } else {
+        (scores.1, scores.0)
+    };
+    season.fixtures.set(fixture, f);
+
+    let diff = scores.0 as i64 - scores.1 as i64;
+    for (i, mut standing) in season.table.iter().enumerate() {
+        if standing.player == *winner {
+            standing.wins += 1;
+            standing.points += WIN_POINTS;
+            standing.diff += diff;
+        } else if standing.player == *loser {
+            standing.losses += 1;
+            standing.diff -= diff;
+        } else {
+            continue;
+        }
+        standing.played += 1;
+        season.table.set(i as u32, standing);
+    }
+    set(env, &season);
+
+    FixtureEvent {
+        id,
+        winner: winner.clone(),
+        fixture,
+        loser: loser.clone(),
+    }
+    .publish(env);
+}
+
+/** Frees up a fixture whose match the admin ended, so it can be replayed. */
+pub fn abort(env: &Env, (id, fixture): (u64, u32), a: &Address, b: &Address) {
+    unlink(env, a, b);
+
+    let mut season = get(env, id);
+    let mut f = season.fixtures.get(fixture).unwrap();
+    f.live = false;
+    season.fixtures.set(fixture, f);
+    set(env, &season);
+}
+
+fn unlink(env: &Env, a: &Address, b: &Address) {
+    let store = env.storage().temporary();
+    for player in [a, b] {
+        let key = UserData::Fixture(player.clone());
+        if store.has(&key) {
+            store.remove(&key);
+        }
+    }
+}
+
+fn get(env: &Env, id: u64) -> Season {
+    match env.storage().persistent().get(&LeagueData::Season(id)) {
+        Some(season) => season,
+        None => panic_with_error!(env, Error::WrongMatch),
+    }
+}
+
+/** Returns the season, panicking if it's over. */
+fn open(env: &Env, id: u64) -> Season {
+    let season = get(env, id);
+    if season.closed || env.ledger().sequence() > season.ends {
+        panic_with_error!(env, Error::SeasonOver);
+    }
+    season
+}
+
+/** Sorts the table by points, then differential, then wins. */
+fn standings(env: &Env, season: &Season) -> Vec<LeagueStanding> {
+    let key = |s: &LeagueStanding| (s.points, s.diff, s.wins);
+
+    let mut sorted: Vec<LeagueStanding> = Vec::new(env);
+    for standing in season.table.iter() {
+        let at = sorted
+            .iter()
+            .position(|s| key(&s) < key(&standing))
+            .unwrap_or(sorted.len() as usize) as u32;
+        sorted.insert(at, standing);
+    }
+    sorted
+}
+
+/**
+ * Pairs every entrant with every other once, using the circle method: one
+ * player stays put while the rest rotate around them, round by round.
+ */
+fn schedule(env: &Env, entrants: &Vec<Address>) -> Vec<Fixture> {
+    // With an odd number of entrants, whoever draws the gap sits out.
+    let mut seats: Vec<Option<Address>> = Vec::new(env);
+    for player in entrants.iter() {
+        seats.push_back(Some(player));
+    }
+    if seats.len() % 2 == 1 {
+        seats.push_back(None);
+    }
+
+    let n = seats.len();
+    let mut fixtures = Vec::new(env);
+    for round in 0..n - 1 {
+        for i in 0..n / 2 {
+            if let (Some(a), Some(b)) = (seats.get(i).unwrap(), seats.get(n - 1 - i).unwrap()) {
+                fixtures.push_back(Fixture {
+                    round: round + 1,
+                    a,
+                    b,
+                    live: false,
+                    winner: None,
+                    a_score: 0,
+                    b_score: 0,
+                });
+            }
+        }
+
+        // Keep the first seat, and move the last one round to the second.
+        let last = seats.pop_back().unwrap();
+        seats.insert(1, last);
+    }
+    fixtures
+}
+
+fn set(env: &Env, season: &Season) {
+    persist(env, &LeagueData::Season(season.id), season);
+}
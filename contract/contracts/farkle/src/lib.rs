@@ -1,8 +1,8 @@
 #![no_std]
-use soroban_sdk::token::{StellarAssetClient};
+use soroban_sdk::token::StellarAssetClient;
 use soroban_sdk::{contract, contracterror, contractevent, contractimpl, contracttype, log};
 use soroban_sdk::{panic_with_error, token};
-use soroban_sdk::{vec, Address, BytesN, Env, Map, Vec};
+use soroban_sdk::{vec, Address, BytesN, Env, IntoVal, Map, Val, Vec};
 
 #[contract]
 pub struct Farkle;
@@ -12,6 +12,18 @@ pub struct Farkle;
 pub enum UserData {
     // persistent
     Balance(Address), // i128 value
+    Rating(Address),  // u32 value, see `rating` module
+    Stats(Address),   // PlayerStats value
+    Bot(Address),     // bool value, set for registered strategy contracts
+
+    HeadToHead(Address, Address), // HeadToHead value, addresses sorted
+
+    Referrer(Address),          // Address value, who referred this player
+    Referrals(Address),         // Referrals value, for this referrer
+    Rewards(Address),           // i128 value, reward tokens waiting to be claimed
+    RewardDay(Address),         // (u64, i128) value, a day and the rewards earned on it
+    Cooldown(Address, Address), // u32 ledger until which the pair earns no rewards, sorted
+    Staker(Address),            // Staker value, see `staking` module
 
     // temporary
     Score(Address),     // u32 value
@@ -22,6 +34,142 @@ pub enum UserData {
 
     // temporary, for tracking forfeits
     LastPlayed(Address), // u32 ledger number
+
+    // temporary, for the match history
+    Started(Address), // u32 ledger number the match began on
+
+    // temporary, for series (see `series` module)
+    Series(Address), // u64 value, the series a match is a game of
+    Stake(Address),  // i128 value, the wager held for this match alone
+    Pulled(Address), // bool value, set if the stake came from the player's wallet
+    Terms(Address),  // MatchTerms value, from this player's side
+
+    // temporary, for the fee vault (see `fees` module)
+    Fee(Address), // MatchFee value, the fee on this player's stake
+
+    // temporary, for tournaments (see `tournament` module)
+    Tournament(Address), // u64 value, the tournament a match is part of
+
+    // temporary, for league seasons (see `league` module)
+    Fixture(Address), // (u64, u32) value, the season and fixture being played
+
+    // temporary, for the doubling cube
+    Cube(Address), // Cube value
+
+    // temporary, for rematches
+    LastMatch(Address),    // LastMatch value
+    RematchOffer(Address), // Address value, who the rematch is offered to
+
+    // temporary, for matches played without a wager
+    Practice(Address), // bool value, set for both players
+
+    // temporary, for matches against the house (see `house` module)
+    HouseStake(Address), // i128 value, what the house matched
+    HouseScore(Address), // u32 value, the house's banked score
+}
+
+/** Who a player last finished a match against, kept around for rematches. */
+#[derive(Clone)]
+#[contracttype]
+pub struct LastMatch {
+    opponent: Address,
+    lost: bool,
+    terms: MatchTerms, // from this player's side
+    practice: bool,
+    pulled: bool, // stakes came from the wallets, as with `engage_from`
+}
+
+/**
+ * What two players agreed to play a match for, as passed to `engage_with`.
+ *
+ * A stronger player can put up more than their opponent, give them a head
+ * start on their score, or play to a higher target.
+ */
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct MatchTerms {
+    pub stake_a: i128,
+    pub stake_b: i128,
+    pub head_start_a: u32, // score `a` starts the match with
+    pub head_start_b: u32,
+    pub target_a: u32, // score `a` needs to win, or zero for the usual 3000
+    pub target_b: u32,
+}
+
+impl MatchTerms {
+    /** The usual terms: equal stakes, no head starts, the same target. */
+    pub fn even(stake: i128) -> MatchTerms {
+        MatchTerms {
+            stake_a: stake,
+            stake_b: stake,
+            head_start_a: 0,
+            head_start_b: 0,
+            target_a: WIN_THRESHOLD,
+            target_b: WIN_THRESHOLD,
+        }
+    }
+
+    /** The same terms, seen from `b`'s side. */
+    pub fn flip(&self) -> MatchTerms {
+        MatchTerms {
+            stake_a: self.stake_b,
+            stake_b: self.stake_a,
+            head_start_a: self.head_start_b,
+            head_start_b: self.head_start_a,
+            target_a: self.target_b,
+            target_b: self.target_a,
+        }
+    }
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum SeriesData {
+    Count,       // instance, u64 number of series started so far
+    Series(u64), // persistent, Series value
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum ContestData {
+    Count,               // instance, u64 number of contests opened so far
+    Contest(u64),        // persistent, Contest value
+    Entry(u64, Address), // persistent, ContestEntry value
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum DailyData {
+    // persistent
+    Commit(u64),         // BytesN<32> value, the SHA-256 hash of the day's seed
+    Seed(u64),           // BytesN<32> value, revealed once the day is over
+    Entry(u64, Address), // DailyEntry value
+    Board(u64),          // Vec<Standing> value, best first
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum TournamentData {
+    Count,           // instance, u64 number of tournaments opened so far
+    Tournament(u64), // persistent, Tournament value
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum LeagueData {
+    Count,       // instance, u64 number of seasons opened so far
+    Season(u64), // persistent, Season value
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum HistoryData {
+    Count, // instance, u64 number of matches recorded so far
+
+    // persistent
+    Result(u64),               // MatchResult value
+    PlayerCount(Address),      // u32 number of matches the player was in
+    PlayerMatch(Address, u32), // u64 value, the nth match the player was in
 }
 
 #[derive(Clone)]
@@ -30,6 +178,28 @@ pub enum AdminData {
     Token,
     Admin,
     Reward,
+    Bankroll,       // Bankroll value
+    Fees,           // i128 fees accrued and not yet collected
+    FeePolicy,      // FeePolicy value, for matches begun from now on
+    FeeSplit,       // Vec<FeeShare> value, for `split_fees`
+    Jackpot,        // i128 value, the jackpot pool
+    JackpotConfig,  // JackpotConfig value
+    ReferralShare,  // u32 per mille of match fees paid to referrers
+    RewardSchedule, // RewardSchedule value
+    RewardEpoch,    // RewardEpoch value, for the epoch rewards were last emitted in
+    RewardGuard,    // RewardGuard value
+    Staking,        // StakingPool value
+}
+
+/** How a match came to an end. */
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum Outcome {
+    Win,      // someone reached the winning score
+    Forfeit,  // the loser stalled past `FORFEIT_DURATION`
+    Concede,  // the loser gave up
+    Aborted,  // the admin ended the match without a winner
+    Deadline, // the loser was behind when a tournament round ran out of time
 }
 
 #[contracterror]
@@ -43,6 +213,21 @@ pub enum Error {
     NotYourTurn = 4,
     WrongMatch = 5,
     BadDieHold = 6,
+    NoRematch = 7,
+    DoublePending = 8,
+    CantDouble = 9,
+    HouseLimit = 10,
+    NotABot = 11,
+    ContestClosed = 12,
+    ContestOpen = 13,
+    TournamentStarted = 14,
+    TournamentFull = 15,
+    RoundInProgress = 16,
+    SeasonOver = 17,
+    SeasonInProgress = 18,
+    AlreadyReferred = 19,
+    RewardsExhausted = 20,
+    BadSeed = 21,
 }
 
 #[contractevent(topics = ["match"], data_format = "single-value")]
@@ -86,13 +271,38 @@ pub struct RerollEvent {
     stop: bool,
 }
 
-#[contractevent(topics = ["win"], data_format = "single-value")]
+#[contractevent(topics = ["win"], data_format = "vec")]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct WinEvent {
     #[topic]
     winner: Address,
 
     score: u32,
+    winner_rating: i32, // change in the winner's rating
+    loser_rating: i32,  // change in the loser's rating
+}
+
+#[contractevent(topics = ["deposit"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DepositEvent {
+    #[topic]
+    player: Address,
+
+    amount: i128,
+    balance: i128, // after the deposit
+}
+
+#[contractevent(topics = ["withdraw"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WithdrawEvent {
+    #[topic]
+    player: Address,
+
+    #[topic]
+    to: Address,
+
+    amount: i128,
+    balance: i128, // left after the withdrawal
 }
 
 const ONE_XLM: i128 = 10_000_000;
@@ -156,7 +366,7 @@ impl Farkle {
 
     /** Returns the current balance a player holds in the game for wagering. */
     pub fn balance(env: &Env, player: Address) -> i128 {
-        Self::check_init(&env);
+        Self::check_init(env);
         let user = UserData::Balance(player);
 
         env.storage().persistent().get(&user).unwrap_or(-1)
@@ -195,6 +405,7 @@ impl Farkle {
         balance += amount;
         store.set(&user, &balance);
 
+        Self::emit_deposit(&env, &by, amount, balance);
         balance
     }
 
@@ -222,12 +433,62 @@ impl Farkle {
 
             // Doing this in the if block avoids an RPC bug that prevents us
             // from removing keys that don't exist.
-            env.storage().persistent().remove(&UserData::Balance(from));
+            env.storage()
+                .persistent()
+                .remove(&UserData::Balance(from.clone()));
+
+            Self::emit_withdraw(&env, &from, &from, balance, 0);
         }
 
         balance
     }
 
+    /**
+     * Withdraws part of an account's funds, possibly to another address.
+     *
+     * Only the available balance can be withdrawn: stakes held for a match
+     * in progress are already out of it.
+     *
+     * # Arguments
+     *
+     * - `from` - The account to withdraw from
+     * - `amount` - How much to withdraw
+     * - `to` - Where to send it
+     *
+     * # Returns
+     *
+     * The balance left in the account.
+     *
+     * # Panics
+     *
+     * - If `amount` isn't positive.
+     * - If the account's available balance doesn't cover it.
+     */
+    pub fn withdraw_amount(env: Env, from: Address, amount: i128, to: Address) -> i128 {
+        if amount <= 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        let balance = Self::balance(&env, from.clone());
+        if balance < amount {
+            panic_with_error!(&env, Error::TooPoor);
+        }
+
+        from.require_auth();
+
+        let contract = env.current_contract_address();
+        let client = token::Client::new(&env, &Self::token(&env));
+        client.transfer(&contract, &to, &amount);
+
+        let left = balance - amount;
+        env.storage()
+            .persistent()
+            .set(&UserData::Balance(from.clone()), &left);
+
+        Self::emit_withdraw(&env, &from, &to, amount, left);
+        left
+    }
+
     /** Returns the current score for a player, assuming they're in a match. */
     pub fn score(env: Env, player: Address) -> u32 {
         env.storage()
@@ -266,19 +527,10 @@ impl Farkle {
      */
     pub fn engage(env: Env, a: Address, b: Address) -> Address {
         Self::check_init(&env);
-        let store = env.storage().temporary();
-
-        // Is A already engaged in a game?
-        let a_opp: Option<Address> = store.get(&UserData::Match(a.clone()));
-        if a_opp.is_some() {
-            panic_with_error!(&env, Error::AlreadyPlaying);
-        }
 
-        // Is B already engaged in a game?
-        let b_opp: Option<Address> = store.get(&UserData::Match(b.clone()));
-        if b_opp.is_some() {
-            panic_with_error!(&env, Error::AlreadyPlaying);
-        }
+        // Is either player already engaged in a game?
+        Self::check_free(&env, &a);
+        Self::check_free(&env, &b);
 
         // Only now require authorization from both parties.
         a.require_auth();
@@ -286,33 +538,212 @@ impl Farkle {
 
         // Lower both players balances so they can't withdraw what they're
         // currently betting in the game.
-        Self::hold_balance(&env, &a);
-        Self::hold_balance(&env, &b);
+        Self::hold_stake(&env, &a, COST_TO_PLAY);
+        Self::hold_stake(&env, &b, COST_TO_PLAY);
+
+        // Roll for who goes first.
+        let first: u64 = env.prng().gen_range(1..=2);
+        Self::start_match(&env, &a, &b, first == 1, &MatchTerms::even(COST_TO_PLAY))
+    }
+
+    /**
+     * Begins a match between two players, pulling each stake straight from
+     * their wallet rather than their deposited balance.
+     *
+     * This works just like `engage`, except that neither player needs to
+     * `deposit` first. Instead, each approves the contract to spend at least
+     * `COST_TO_PLAY` of their XLM (with the token's `approve`), and the stake
+     * is taken with `transfer_from`. Winnings are paid to the winner's wallet
+     * as usual, and a stake that's refunded goes back to the wallet too.
+     *
+     * # Returns
+     *
+     * The address of the player who should go (call `roll`) first.
+     *
+     * # Panics
+     *
+     * - If either player is already in a game.
+     * - If a player's allowance or wallet doesn't cover the stake.
+     */
+    pub fn engage_from(env: Env, a: Address, b: Address) -> Address {
+        Self::check_init(&env);
 
-        // Mark players as being in a match against each other.
-        store.set(&UserData::Match(a.clone()), &b);
-        store.set(&UserData::Match(b.clone()), &a);
+        Self::check_free(&env, &a);
+        Self::check_free(&env, &b);
 
-        // Overwrite any previous dice rolls, in case of bugs.
-        let empty: Vec<u32> = Vec::new(&env);
-        store.set(&UserData::Dice(a.clone()), &empty);
-        store.set(&UserData::Dice(b.clone()), &empty);
+        a.require_auth();
+        b.require_auth();
 
-        let seq = env.ledger().sequence();
-        store.set(&UserData::LastPlayed(a.clone()), &seq);
-        store.set(&UserData::LastPlayed(b.clone()), &seq);
+        Self::pull_stake(&env, &a, COST_TO_PLAY);
+        Self::pull_stake(&env, &b, COST_TO_PLAY);
+
+        let first: u64 = env.prng().gen_range(1..=2);
+        Self::start_match(&env, &a, &b, first == 1, &MatchTerms::even(COST_TO_PLAY))
+    }
+
+    /**
+     * Begins a practice match between two players.
+     *
+     * This plays exactly like a match begun with `engage`, but nothing is
+     * wagered: no balance is held, no fee is charged, and no rewards are
+     * handed out at the end. Nor does it count toward ratings, stats or
+     * head-to-head records. Players don't need to deposit anything first.
+     *
+     * # Returns
+     *
+     * The address of the player who should go (call `roll`) first.
+     *
+     * # Panics
+     *
+     * - If either player is already in a game.
+     */
+    pub fn practice_engage(env: Env, a: Address, b: Address) -> Address {
+        Self::check_init(&env);
+
+        Self::check_free(&env, &a);
+        Self::check_free(&env, &b);
+
+        a.require_auth();
+        b.require_auth();
+
+        let first: u64 = env.prng().gen_range(1..=2);
+        let first = Self::start_match(&env, &a, &b, first == 1, &MatchTerms::even(0));
+        Self::mark_practice(&env, &a, &b);
+        first
+    }
+
+    /**
+     * Begins a match between two players on custom terms.
+     *
+     * This works just like `engage`, except each player puts up their own
+     * stake and can be given a head start or a different target score. Both
+     * players authorize the exact `terms`, and the winner takes both stakes.
+     *
+     * # Arguments
+     *
+     * `a` - The player on one side of the match
+     * `b` - The player on the other side of the match
+     * `terms` - The stakes and handicaps for each player
+     *
+     * # Returns
+     *
+     * The address of the player who should go (call `roll`) first.
+     *
+     * # Panics
+     *
+     * - If either stake is less than `COST_TO_PLAY`, or a head start reaches
+     *   its target.
+     * - If either player is already in a game.
+     * - If a player doesn't have their stake deposited.
+     */
+    pub fn engage_with(env: Env, a: Address, b: Address, terms: MatchTerms) -> Address {
+        Self::check_init(&env);
+
+        let mut terms = terms;
+        for target in [&mut terms.target_a, &mut terms.target_b] {
+            if *target == 0 {
+                *target = WIN_THRESHOLD;
+            }
+        }
+        Self::check_stake(&env, terms.stake_a);
+        Self::check_stake(&env, terms.stake_b);
+        if terms.head_start_a >= terms.target_a || terms.head_start_b >= terms.target_b {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        Self::check_free(&env, &a);
+        Self::check_free(&env, &b);
+
+        a.require_auth();
+        b.require_auth();
+
+        Self::hold_stake(&env, &a, terms.stake_a);
+        Self::hold_stake(&env, &b, terms.stake_b);
 
-        // Roll for who goes first.
         let first: u64 = env.prng().gen_range(1..=2);
-        if first == 1 {
-            store.set(&UserData::Turn(a.clone()), &a);
-            store.set(&UserData::Turn(b.clone()), &a);
+        Self::start_match(&env, &a, &b, first == 1, &terms)
+    }
+
+    /**
+     * Offers the opponent from your last match another one, on the same terms.
+     *
+     * The offer stands for as long as a player has to make a move (see
+     * `FORFEIT_DURATION`), and the opponent takes it up with `accept_rematch`.
+     *
+     * # Panics
+     *
+     * - If this `player` is in a match or didn't just finish one.
+     */
+    pub fn offer_rematch(env: Env, player: Address) {
+        Self::check_init(&env);
+        Self::check_free(&env, &player);
+
+        let store = env.storage().temporary();
+        let last: LastMatch = match store.get(&UserData::LastMatch(player.clone())) {
+            Some(last) => last,
+            None => panic_with_error!(&env, Error::NoRematch),
+        };
+
+        player.require_auth();
+
+        let key = UserData::RematchOffer(player);
+        store.set(&key, &last.opponent);
+        store.extend_ttl(&key, FORFEIT_DURATION, FORFEIT_DURATION);
+    }
+
+    /**
+     * Accepts a rematch offered by the opponent from your last match.
+     *
+     * Both wagers are held again from wherever they came from last time:
+     * the players' deposited balances, or their wallets if the last match
+     * was begun with `engage_from`. Whoever lost the last match goes first.
+     *
+     * # Returns
+     *
+     * The address of the player who should go (call `roll`) first.
+     *
+     * # Panics
+     *
+     * - If either player is already in a game.
+     * - If the opponent hasn't offered (or has since withdrawn from) a rematch.
+     * - If a player doesn't have a sufficient amount deposited (or allowed)
+     *   to wager a game.
+     */
+    pub fn accept_rematch(env: Env, player: Address) -> Address {
+        Self::check_init(&env);
+        let store = env.storage().temporary();
+
+        let last: LastMatch = match store.get(&UserData::LastMatch(player.clone())) {
+            Some(last) => last,
+            None => panic_with_error!(&env, Error::NoRematch),
+        };
+        let opp = last.opponent;
+
+        let offer: Option<Address> = store.get(&UserData::RematchOffer(opp.clone()));
+        if offer.as_ref() != Some(&player) {
+            panic_with_error!(&env, Error::NoRematch);
+        }
+
+        Self::check_free(&env, &player);
+        Self::check_free(&env, &opp);
+        player.require_auth();
+
+        if last.practice {
+            let first = Self::start_match(&env, &player, &opp, last.lost, &last.terms);
+            Self::mark_practice(&env, &player, &opp);
+            return first;
+        }
+
+        // Stakes come from wherever they came from last time.
+        if last.pulled {
+            Self::pull_stake(&env, &player, last.terms.stake_a);
+            Self::pull_stake(&env, &opp, last.terms.stake_b);
         } else {
-            store.set(&UserData::Turn(a.clone()), &b);
-            store.set(&UserData::Turn(b.clone()), &b);
+            Self::hold_stake(&env, &player, last.terms.stake_a);
+            Self::hold_stake(&env, &opp, last.terms.stake_b);
         }
 
-        Self::emit_match(&env, &a, &b, first == 1)
+        Self::start_match(&env, &player, &opp, last.lost, &last.terms)
     }
 
     pub fn forfeit(env: Env, opponent: Address) -> bool {
@@ -320,13 +751,21 @@ impl Farkle {
 
         // Who's the opponent playing against?
         let player = Self::get_opp(&env, opponent.clone());
-        player.require_auth(); // make sure the right person initiated this
+        if house::is(&env, &player) || bot::is(&env, &player) {
+            // Neither the house nor a bot can sign for itself, so the admin
+            // (who registers bots) claims for them.
+            let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+            admin.require_auth();
+        } else {
+            player.require_auth(); // make sure the right person initiated this
+        }
 
         // When's the last time the opponent took an action?
         let last_seq: u32 = store.get(&UserData::LastPlayed(opponent.clone())).unwrap();
 
-        // Is it currently their turn?
-        let playing: Address = store.get(&UserData::Turn(opponent.clone())).unwrap();
+        // Is it currently their turn (or their move on the doubling cube)?
+        let turn: Address = store.get(&UserData::Turn(opponent.clone())).unwrap();
+        let playing = cube::to_act(&env, &opponent, turn);
         if playing != opponent {
             // If it's not the opponent's turn, they aren't stalling.
             return false;
@@ -337,16 +776,59 @@ impl Farkle {
             return false;
         }
 
-        Self::_end_match(&env, opponent, player.clone());
-        let t = Self::token(&env);
-        let payout = Self::get_payout();
-        let client = token::Client::new(&env, &t);
-        let contract = env.current_contract_address();
-        client.transfer(&contract, &player, &payout);
+        Self::settle(&env, &player, &opponent, 0, Outcome::Forfeit);
+        true
+    }
+
+    /**
+     * Gives up the match, handing the win (and the pot) to the opponent.
+     *
+     * Unlike `forfeit`, this can be done at any time, whoever's turn it is.
+     *
+     * # Panics
+     *
+     * - If this `player` isn't in a match.
+     */
+    pub fn concede(env: Env, player: Address) {
+        Self::check_init(&env);
 
-        Self::emit_win(&env, &player, 0);
+        let opp = Self::get_opp(&env, player.clone());
+        player.require_auth();
 
-        true
+        Self::settle(&env, &opp, &player, 0, Outcome::Concede);
+    }
+
+    /** Returns the Elo rating of a player, with newcomers starting at 1200. */
+    pub fn rating(env: Env, player: Address) -> u32 {
+        rating::get(&env, &player)
+    }
+
+    /** Returns the lifetime statistics of a player. */
+    pub fn stats(env: Env, player: Address) -> PlayerStats {
+        stats::get(&env, &player)
+    }
+
+    /** Returns the record of every match between two players, from `a`'s side. */
+    pub fn head_to_head(env: Env, a: Address, b: Address) -> HeadToHead {
+        head_to_head::get(&env, &a, &b)
+    }
+
+    /**
+     * Returns a page of the matches a player has finished, newest first.
+     *
+     * # Arguments
+     *
+     * - `player` - Whose matches to list.
+     * - `cursor` - How many of the most recent matches to skip.
+     * - `limit` - How many matches to return, at most 20.
+     */
+    pub fn match_history(env: Env, player: Address, cursor: u32, limit: u32) -> Vec<MatchResult> {
+        history::for_player(&env, &player, cursor, limit)
+    }
+
+    /** Like `match_history`, but for every match played in this game. */
+    pub fn recent_matches(env: Env, cursor: u32, limit: u32) -> Vec<MatchResult> {
+        history::recent(&env, cursor, limit)
     }
 
     /**
@@ -392,37 +874,220 @@ impl Farkle {
      */
     pub fn roll(env: Env, player: Address, save: Vec<u32>, stop: bool) -> Vec<u32> {
         Self::check_init(&env);
-        let store = env.storage().temporary();
-
-        // Is this player even in a match?
-        let opp: Address = Self::get_opp(&env, player.clone());
-
-        // Is it their turn?
-        let _: Address = match store.get(&UserData::Turn(player.clone())) {
-            Some(turn) => {
-                if turn != player {
-                    panic_with_error!(&env, Error::NotYourTurn)
-                }
-                turn
-            }
-            None => panic_with_error!(&env, Error::WrongMatch),
-        };
+        let opp = Self::check_turn(&env, &player);
 
         player.require_auth();
-        Self::bump_match_ttl(&env, player.clone(), opp.clone());
+        Self::take_roll(&env, player, opp, save, stop)
+    }
+
+    /** Returns the token address being used for wagers. */
+    pub fn token(env: &Env) -> Address {
+        env.storage().instance().get(&AdminData::Token).unwrap()
+    }
+
+    /** Panics if the contract isn't initialized. */
+    pub fn check_init(env: &Env) {
+        if !env.storage().instance().has(&AdminData::Admin) {
+            panic_with_error!(env, Error::NotInitialized);
+        }
+        env.storage().instance().extend_ttl(
+            60 * 60 * 24,         /* if < 1 day */
+            60 * 60 * 24 * 7 / 5, /* ~7 days */
+        );
+    }
+
+    /** Bumps the time-to-live for a match between two players. */
+    pub fn bump_match_ttl(env: &Env, a: Address, b: Address) {
+        let tstore = env.storage().temporary();
+        let pstore = env.storage().persistent();
+
+        tstore.set(&UserData::LastPlayed(a.clone()), &env.ledger().sequence());
+
+        for addr in vec![&env, a, b] {
+            let mut key = UserData::Score(addr.clone());
+            if tstore.has(&key) {
+                tstore.extend_ttl(&key, 15, 100);
+            }
+
+            key = UserData::TurnScore(addr.clone());
+            if tstore.has(&key) {
+                tstore.extend_ttl(&key, 15, 100);
+            }
+
+            key = UserData::Dice(addr.clone());
+            if tstore.has(&key) {
+                tstore.extend_ttl(&key, 15, 100);
+            }
+
+            key = UserData::Match(addr.clone());
+            if tstore.has(&key) {
+                tstore.extend_ttl(&key, 15, 100);
+            }
+
+            key = UserData::Turn(addr.clone());
+            if tstore.has(&key) {
+                tstore.extend_ttl(&key, 15, 100);
+            }
+
+            key = UserData::Started(addr.clone());
+            if tstore.has(&key) {
+                tstore.extend_ttl(&key, 15, 100);
+            }
+
+            key = UserData::Stake(addr.clone());
+            if tstore.has(&key) {
+                tstore.extend_ttl(&key, 15, 100);
+            }
+
+            key = UserData::Series(addr.clone());
+            if tstore.has(&key) {
+                tstore.extend_ttl(&key, 15, 100);
+            }
+
+            key = UserData::Cube(addr.clone());
+            if tstore.has(&key) {
+                tstore.extend_ttl(&key, 15, 100);
+            }
+
+            key = UserData::Terms(addr.clone());
+            if tstore.has(&key) {
+                tstore.extend_ttl(&key, 15, 100);
+            }
+
+            for key in [
+                UserData::Tournament(addr.clone()),
+                UserData::Fixture(addr.clone()),
+                UserData::Fee(addr.clone()),
+                UserData::Pulled(addr.clone()),
+                UserData::Practice(addr.clone()),
+                UserData::HouseStake(addr.clone()),
+                UserData::HouseScore(addr.clone()),
+            ] {
+                if tstore.has(&key) {
+                    tstore.extend_ttl(&key, 15, 100);
+                }
+            }
+
+            for key in [
+                UserData::Rating(addr.clone()),
+                UserData::Stats(addr.clone()),
+            ] {
+                if pstore.has(&key) {
+                    pstore.extend_ttl(
+                        &key,
+                        60 * 60 * 24,         /* if < 1 day */
+                        60 * 60 * 24 * 7 / 5, /* ~7 days */
+                    );
+                }
+            }
+
+            key = UserData::Balance(addr);
+            if pstore.has(&key) {
+                pstore.extend_ttl(
+                    &key,
+                    60 * 60 * 24,         /* if < 1 day */
+                    60 * 60 * 24 * 7 / 5, /* ~7 days */
+                );
+            }
+        }
+
+        Self::check_init(env);
+    }
+
+    // Allows the admin to force a game to end, which helps resolve bugs.
+    pub fn end_match(env: &Env, player: Address, opp: Address) -> bool {
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        let matched: Option<Address> = env
+            .storage()
+            .temporary()
+            .get(&UserData::Match(player.clone()));
+        if matched.as_ref() == Some(&opp) {
+            history::record(env, &player, &opp, None, Outcome::Aborted, 0);
+            head_to_head::record(env, &player, &opp, None, Outcome::Aborted);
+
+            if let Some(id) = series::current(env, &player) {
+                series::abort(env, id);
+            }
+
+            // Stakes go back, less any fee already charged on them.
+            for (p, o) in [(&player, &opp), (&opp, &player)] {
+                let refund = Self::held(env, p, o) - fees::charged(env, p);
+                if refund > 0 && !house::is(env, p) {
+                    Self::refund(env, p, refund);
+                }
+            }
+
+            // The pairing is left to be settled by the round's deadline.
+            tournament::unlink(env, &player, &opp);
+
+            // Fixtures can be played again.
+            if let Some(fixture) = league::current(env, &player) {
+                league::abort(env, fixture, &player, &opp);
+            }
+
+            // The house keeps what it matched, but no longer has it at stake.
+            for (p, o) in [(&player, &opp), (&opp, &player)] {
+                if house::is(env, o) {
+                    house::release(env, p, 0);
+                }
+            }
+        }
+
+        Self::_end_match(env, player, opp)
+    }
+
+    /**
+     * Panics unless it's `player`'s turn to roll in their match.
+     *
+     * # Returns
+     *
+     * Their opponent.
+     */
+    fn check_turn(env: &Env, player: &Address) -> Address {
+        // Is this player even in a match?
+        let opp: Address = Self::get_opp(env, player.clone());
+
+        // Is it their turn?
+        match env
+            .storage()
+            .temporary()
+            .get::<_, Address>(&UserData::Turn(player.clone()))
+        {
+            Some(turn) => {
+                if turn != *player {
+                    panic_with_error!(env, Error::NotYourTurn)
+                }
+            }
+            None => panic_with_error!(env, Error::WrongMatch),
+        };
+
+        // Are they waiting on an answer to doubling the stakes?
+        if cube::get(env, player).offered.is_some() {
+            panic_with_error!(env, Error::DoublePending);
+        }
+
+        opp
+    }
+
+    /** Carries out a `roll` for a `player` who may take it (see `check_turn`). */
+    fn take_roll(env: &Env, player: Address, opp: Address, save: Vec<u32>, stop: bool) -> Vec<u32> {
+        let store = env.storage().temporary();
+        Self::bump_match_ttl(env, player.clone(), opp.clone());
 
         // What was their roll last turn?
         let last_roll: Vec<u32> = match store.get(&UserData::Dice(player.clone())) {
             Some(dice) => dice,
-            None => Vec::new(&env),
+            None => Vec::new(env),
         };
 
-        log!(&env, "Last roll by", player, last_roll);
-        let mut roll: Vec<u32> = Vec::new(&env);
+        log!(env, "Last roll by", player, last_roll);
+        let mut roll: Vec<u32> = Vec::new(env);
         let mut roll_count: u32;
 
         // Was there a previous roll?
-        if last_roll.len() > 0 {
+        if !last_roll.is_empty() {
             let mut turn_score: u32 = store.get(&UserData::TurnScore(player.clone())).unwrap_or(0);
 
             //
@@ -430,30 +1095,21 @@ impl Farkle {
             // pass+score, or keep a dice combo and re-roll.
             //
 
-            if save.len() > last_roll.len() {
-                panic_with_error!(&env, Error::BadDieHold)
-            }
-            if save.len() == 0 && !stop {
-                panic_with_error!(&env, Error::BadDieHold);
+            if save.is_empty() && !stop {
+                panic_with_error!(env, Error::BadDieHold);
             }
 
-            let mut saved: Vec<u32> = Vec::new(&env);
-            for dice_idx in save.into_iter() {
-                saved.push_back(match last_roll.get(dice_idx) {
-                    Some(x) => x,
-                    None => panic_with_error!(&env, Error::BadDieHold),
-                });
-            }
+            let saved = Self::saved_dice(env, &last_roll, &save);
 
             roll_count = last_roll.len() - saved.len();
-            let roll_score = Self::score_turn(&env, &saved, true);
+            let roll_score = Self::score_turn(env, &saved, true);
             if roll_score == 0 && !stop {
-                panic_with_error!(&env, Error::BadDieHold);
+                panic_with_error!(env, Error::BadDieHold);
             }
 
-            Self::emit_reroll(&env, &player, &saved, roll_score, stop);
+            Self::emit_reroll(env, &player, &saved, roll_score, stop);
 
-            turn_score += roll_score as u32;
+            turn_score += roll_score;
 
             if !stop {
                 store.set(&UserData::TurnScore(player.clone()), &turn_score);
@@ -461,37 +1117,30 @@ impl Farkle {
                 // Fresh re-roll; we're still still mid-turn.
                 if roll_count == 0 {
                     roll_count = 6;
+                    if !Self::practicing(env, &player) {
+                        stats::record_hot_dice(env, &player);
+                    }
                 }
             } else {
                 let score = store.get(&UserData::Score(player.clone())).unwrap_or(0) + turn_score;
+                if !Self::practicing(env, &player) {
+                    stats::record_bank(env, &player, turn_score, score);
+                }
 
                 // Did the player win? If so, transfer their winnings and
                 // end the game.
-                if score >= WIN_THRESHOLD {
-                    let t = Self::token(&env);
-                    let client = token::Client::new(&env, &t);
-                    let payout = Self::get_payout();
-
-                    let contract = env.current_contract_address();
-                    client.transfer(&contract, &player, &payout);
-
-                    // Both players get rewards for participation (so millenial
-                    // coded..), but the winner gets double the base reward.
-                    let r = REWARD * 2;
-
-                    let reward_addr: Address = env.storage().instance().get(&AdminData::Reward).unwrap();
-                    let reward = StellarAssetClient::new(&env, &reward_addr);
-
-                    reward.transfer(&contract, &player, &r);
-                    reward.transfer(&contract, &opp, &REWARD);
-
-                    Self::_end_match(&env, player.clone(), opp);
-                    Self::emit_win(&env, &player, score);
+                if score >= Self::target(env, &player) {
+                    Self::send_rewards(env, &player, &opp);
+                    Self::settle(env, &player, &opp, score, Outcome::Win);
                 } else {
                     // Update the new score.
                     store.set(&UserData::Score(player.clone()), &score);
-                    Self::pass_turn(&env, player.clone(), opp);
-                    Self::emit_roll(&env, player.clone(), &roll);
+                    Self::pass_turn(env, player.clone(), opp.clone());
+                    Self::emit_roll(env, player.clone(), &roll);
+
+                    if house::is(env, &opp) {
+                        house::play(env, &player);
+                    }
                 }
 
                 return roll;
@@ -501,92 +1150,117 @@ impl Farkle {
             roll_count = 6;
         }
 
-        for _i in 1..=roll_count {
-            let die: u64 = env.prng().gen_range(1..=6);
-            roll.push_back(die as u32);
-        }
+        roll = Self::roll_dice(env, roll_count);
+        jackpot::check(env, &player, &opp, &roll, last_roll.is_empty());
 
         // If they bust out immediately, end the turn early.
-        if Self::score_turn(&env, &roll, false) == 0 {
-            Self::pass_turn(&env, player.clone(), opp);
-            Self::emit_bust(&env, player.clone(), &roll);
+        if Self::score_turn(env, &roll, false) == 0 {
+            Self::pass_turn(env, player.clone(), opp.clone());
+            if !Self::practicing(env, &player) {
+                stats::record_bust(env, &player);
+            }
+            Self::emit_bust(env, player.clone(), &roll);
+
+            if house::is(env, &opp) {
+                house::play(env, &player);
+            }
         } else {
             // Store the last roll.
             store.set(&UserData::Dice(player.clone()), &roll);
-            Self::emit_roll(&env, player.clone(), &roll);
+            Self::emit_roll(env, player.clone(), &roll);
         }
 
         roll
     }
 
-    /** Returns the token address being used for wagers. */
-    pub fn token(env: &Env) -> Address {
-        env.storage().instance().get(&AdminData::Token).unwrap()
+    /** Marks the match just started between `a` and `b` as unwagered. */
+    fn mark_practice(env: &Env, a: &Address, b: &Address) {
+        let store = env.storage().temporary();
+        store.set(&UserData::Practice(a.clone()), &true);
+        store.set(&UserData::Practice(b.clone()), &true);
     }
 
-    /** Panics if the contract isn't initialized. */
-    pub fn check_init(env: &Env) {
-        if !env.storage().instance().has(&AdminData::Admin) {
-            panic_with_error!(env, Error::NotInitialized);
+    /** Returns whether `player` is in a practice match. */
+    fn practicing(env: &Env, player: &Address) -> bool {
+        env.storage()
+            .temporary()
+            .has(&UserData::Practice(player.clone()))
+    }
+
+    /**
+     * Panics unless `stake` is at least `COST_TO_PLAY`. Smaller stakes would
+     * pay next to no fee, but earn full rewards.
+     */
+    fn check_stake(env: &Env, stake: i128) {
+        if stake < COST_TO_PLAY {
+            panic_with_error!(env, Error::InvalidAmount);
         }
-        env.storage().instance().extend_ttl(
-            60 * 60 * 24,         /* if < 1 day */
-            60 * 60 * 24 * 7 / 5, /* ~7 days */
-        );
     }
 
-    /** Bumps the time-to-live for a match between two players. */
-    pub fn bump_match_ttl(env: &Env, a: Address, b: Address) {
-        let tstore = env.storage().temporary();
-        let pstore = env.storage().persistent();
+    /** Panics if `player` is already in a match. */
+    fn check_free(env: &Env, player: &Address) {
+        if env
+            .storage()
+            .temporary()
+            .has(&UserData::Match(player.clone()))
+        {
+            panic_with_error!(env, Error::AlreadyPlaying);
+        }
+    }
 
-        tstore.set(&UserData::LastPlayed(a.clone()), &env.ledger().sequence());
+    /**
+     * Sets up a match between two players whose stakes under `terms` are
+     * already held, with `a` going first if `a_first` is set. Returns the
+     * first player.
+     */
+    fn start_match(
+        env: &Env,
+        a: &Address,
+        b: &Address,
+        a_first: bool,
+        terms: &MatchTerms,
+    ) -> Address {
+        let store = env.storage().temporary();
+        let seq = env.ledger().sequence();
+        let first = if a_first { a } else { b };
 
-        for addr in vec![&env, a, b] {
-            let mut key = UserData::Score(addr.clone());
-            if tstore.has(&key) {
-                tstore.extend_ttl(&key, 15, 100);
+        for (player, opp, terms) in [(a, b, terms.clone()), (b, a, terms.flip())] {
+            // The house plays many matches at once, so it keeps no state of
+            // its own (see `house` module).
+            if house::is(env, player) {
+                continue;
             }
 
-            key = UserData::TurnScore(addr.clone());
-            if tstore.has(&key) {
-                tstore.extend_ttl(&key, 15, 100);
+            store.set(&UserData::Stake(player.clone()), &terms.stake_a);
+            if terms.head_start_a > 0 {
+                store.set(&UserData::Score(player.clone()), &terms.head_start_a);
             }
+            store.set(&UserData::Terms(player.clone()), &terms);
 
-            key = UserData::Dice(addr.clone());
-            if tstore.has(&key) {
-                tstore.extend_ttl(&key, 15, 100);
-            }
+            // Mark players as being in a match against each other.
+            store.set(&UserData::Match(player.clone()), opp);
 
-            key = UserData::Match(addr.clone());
-            if tstore.has(&key) {
-                tstore.extend_ttl(&key, 15, 100);
-            }
+            // Overwrite any previous dice rolls, in case of bugs.
+            store.set(&UserData::Dice(player.clone()), &Vec::<u32>::new(env));
 
-            key = UserData::Turn(addr.clone());
-            if tstore.has(&key) {
-                tstore.extend_ttl(&key, 15, 100);
-            }
+            store.set(&UserData::LastPlayed(player.clone()), &seq);
+            store.set(&UserData::Started(player.clone()), &seq);
+            store.set(&UserData::Turn(player.clone()), first);
+        }
 
-            key = UserData::Balance(addr);
-            if pstore.has(&key) {
-                pstore.extend_ttl(
-                    &key,
-                    60 * 60 * 24,         /* if < 1 day */
-                    60 * 60 * 24 * 7 / 5, /* ~7 days */
-                );
+        // Any rematch between these two has now been played out.
+        for addr in [a, b] {
+            for key in [
+                UserData::LastMatch(addr.clone()),
+                UserData::RematchOffer(addr.clone()),
+            ] {
+                if store.has(&key) {
+                    store.remove(&key);
+                }
             }
         }
 
-        Self::check_init(env);
-    }
-
-    // Allows the admin to force a game to end, which helps resolve bugs.
-    pub fn end_match(env: &Env, player: Address, opp: Address) -> bool {
-        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
-        admin.require_auth();
-
-        Self::_end_match(env, player, opp)
+        Self::emit_match(env, a, b, a_first)
     }
 
     fn pass_turn(env: &Env, from: Address, to: Address) {
@@ -602,11 +1276,111 @@ impl Farkle {
 
         // Remove their last roll.
         key = UserData::Dice(from.clone());
-        store.set(&key, &Vec::<u32>::new(&env));
+        store.set(&key, &Vec::<u32>::new(env));
 
         // Pass the turn.
         store.set(&UserData::Turn(from), &to);
-        store.set(&UserData::Turn(to.clone()), &to);
+        if !house::is(env, &to) {
+            store.set(&UserData::Turn(to.clone()), &to);
+        }
+    }
+
+    /**
+     * Pays the pot out to `winner`, closes the match for both players, and
+     * updates their ratings and stats accordingly.
+     */
+    fn settle(env: &Env, winner: &Address, loser: &Address, score: u32, outcome: Outcome) {
+        let store = env.storage().temporary();
+        let stake = Self::held(env, winner, loser);
+        let lost = Self::held(env, loser, winner);
+
+        // Games of a series are only paid out when the series is, and the
+        // house's winnings stay in its bankroll. The house's own stake
+        // carries no fee.
+        fees::settle(env, winner, loser);
+        let (fee, lost_fee) = (fees::on_stake(env, winner), fees::on_stake(env, loser));
+        let payout = if stake > 0 {
+            stake + lost - fee - lost_fee
+        } else {
+            0
+        };
+        if payout > 0 && !house::is(env, winner) {
+            let client = token::Client::new(env, &Self::token(env));
+            let contract = env.current_contract_address();
+            client.transfer(&contract, winner, &payout);
+        }
+
+        let series = series::current(env, winner);
+        let tournament = tournament::current(env, winner);
+        let fixture = league::current(env, winner);
+        let scores = (
+            score.max(Self::banked(env, winner, loser)),
+            Self::banked(env, loser, winner),
+        );
+        let practice = Self::practicing(env, winner);
+        let pulled = Self::pulled(env, winner);
+        let terms: Option<MatchTerms> = store.get(&UserData::Terms(winner.clone()));
+        history::record(
+            env,
+            winner,
+            loser,
+            Some(winner.clone()),
+            outcome.clone(),
+            score,
+        );
+        if house::is(env, winner) {
+            house::release(env, loser, lost - lost_fee);
+        } else if house::is(env, loser) {
+            house::release(env, winner, -lost);
+        }
+        Self::_end_match(env, winner.clone(), loser.clone());
+
+        // Practice counts for nothing, and a match cut short by a deadline
+        // says little about either player.
+        if !practice {
+            stats::record_result(env, winner, loser, &outcome, (stake, lost), payout);
+            head_to_head::record(env, winner, loser, Some(winner.clone()), outcome.clone());
+        }
+        let (gain, loss) = if practice || outcome == Outcome::Deadline {
+            (0, 0)
+        } else {
+            rating::record_win(env, winner, loser)
+        };
+        Self::emit_win(env, winner, score, gain, loss);
+
+        if let Some(id) = series {
+            series::record_game(env, id, winner, loser);
+            return;
+        }
+        if let Some(id) = tournament {
+            tournament::record_game(env, id, winner, loser);
+            return;
+        }
+        if let Some(fixture) = fixture {
+            league::record_game(env, fixture, winner, loser, scores);
+            return;
+        }
+        if house::is(env, winner) || house::is(env, loser) {
+            return;
+        }
+
+        // Leave enough behind for the two to agree on a rematch.
+        let terms = terms.unwrap_or(MatchTerms::even(COST_TO_PLAY));
+        for (player, opponent, lost, terms) in [
+            (winner, loser, false, terms.clone()),
+            (loser, winner, true, terms.flip()),
+        ] {
+            let key = UserData::LastMatch(player.clone());
+            let last = LastMatch {
+                opponent: opponent.clone(),
+                lost,
+                terms,
+                practice,
+                pulled,
+            };
+            store.set(&key, &last);
+            store.extend_ttl(&key, FORFEIT_DURATION, FORFEIT_DURATION);
+        }
     }
 
     fn _end_match(env: &Env, player: Address, opp: Address) -> bool {
@@ -644,11 +1418,67 @@ impl Farkle {
         if store.has(&key) {
             store.remove(&key);
         }
-        key = UserData::LastPlayed(player);
+        key = UserData::LastPlayed(player.clone());
+        if store.has(&key) {
+            store.remove(&key);
+        }
+        key = UserData::LastPlayed(opp.clone());
+        if store.has(&key) {
+            store.remove(&key);
+        }
+        key = UserData::Started(player.clone());
+        if store.has(&key) {
+            store.remove(&key);
+        }
+        key = UserData::Started(opp.clone());
+        if store.has(&key) {
+            store.remove(&key);
+        }
+        key = UserData::Stake(player.clone());
+        if store.has(&key) {
+            store.remove(&key);
+        }
+        key = UserData::Stake(opp.clone());
+        if store.has(&key) {
+            store.remove(&key);
+        }
+        key = UserData::Cube(player.clone());
+        if store.has(&key) {
+            store.remove(&key);
+        }
+        key = UserData::Cube(opp.clone());
+        if store.has(&key) {
+            store.remove(&key);
+        }
+        key = UserData::Terms(player.clone());
+        if store.has(&key) {
+            store.remove(&key);
+        }
+        key = UserData::Terms(opp.clone());
         if store.has(&key) {
             store.remove(&key);
         }
-        key = UserData::LastPlayed(opp);
+        key = UserData::Fee(player.clone());
+        if store.has(&key) {
+            store.remove(&key);
+        }
+        key = UserData::Fee(opp.clone());
+        if store.has(&key) {
+            store.remove(&key);
+        }
+        key = UserData::Pulled(player.clone());
+        if store.has(&key) {
+            store.remove(&key);
+        }
+        key = UserData::Pulled(opp.clone());
+        if store.has(&key) {
+            store.remove(&key);
+        }
+        key = UserData::Practice(player);
+        if store.has(&key) {
+            store.remove(&key);
+        }
+        key = UserData::Practice(opp);
         if store.has(&key) {
             store.remove(&key);
         }
@@ -656,32 +1486,197 @@ impl Farkle {
         rv
     }
 
-    fn hold_balance(env: &Env, player: &Address) {
-        // Pull player balance and hold in contract
-        let mut balance: i128 = Self::balance(&env, player.clone());
-        if balance < COST_TO_PLAY {
-            panic_with_error!(env, Error::TooPoor); // too poor to play
+    /**
+     * Takes `amount` out of a player's balance, e.g. to enter a contest,
+     * charging the fee on it.
+     */
+    fn hold_balance(env: &Env, player: &Address, amount: i128) {
+        Self::debit(env, player, amount);
+
+        // The play fee (1%) stays in the contract until it's collected.
+        fees::accrue_entry(env, player, Self::get_fee(amount));
+    }
+
+    /**
+     * Takes `amount` out of a player's balance to hold as their stake in a
+     * match, with the fee on it charged as the match's fee policy says.
+     */
+    fn hold_stake(env: &Env, player: &Address, amount: i128) {
+        Self::debit(env, player, amount);
+        fees::hold(env, player, amount);
+    }
+
+    /**
+     * Pulls `amount` from a player's wallet, against the allowance they gave
+     * the contract, to hold as their stake in a match.
+     */
+    fn pull_stake(env: &Env, player: &Address, amount: i128) {
+        let contract = env.current_contract_address();
+        let client = token::Client::new(env, &Self::token(env));
+        client.transfer_from(&contract, player, &contract, &amount);
+
+        fees::hold(env, player, amount);
+        env.storage()
+            .temporary()
+            .set(&UserData::Pulled(player.clone()), &true);
+    }
+
+    /**
+     * Holds `amount` more as a player's stake in a match, taking it from
+     * wherever the rest of their stake came from.
+     */
+    fn raise_stake(env: &Env, player: &Address, amount: i128) {
+        if Self::pulled(env, player) {
+            Self::pull_stake(env, player, amount);
+        } else {
+            Self::hold_stake(env, player, amount);
+        }
+    }
+
+    /** Whether a player could put up `amount` more for `raise_stake`. */
+    fn can_raise(env: &Env, player: &Address, amount: i128) -> bool {
+        if !Self::pulled(env, player) {
+            return Self::balance(env, player.clone()) >= amount;
         }
-        balance -= COST_TO_PLAY;
 
-        // Transfer the play fee (1%) to the admin.
-        let fee = Self::get_fee();
-        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
         let contract = env.current_contract_address();
-        let client = token::Client::new(&env, &Self::token(&env));
-        client.transfer(&contract, &admin, &fee);
+        let client = token::Client::new(env, &Self::token(env));
+        client.allowance(player, &contract) >= amount && client.balance(player) >= amount
+    }
+
+    /** Whether a player's stake in their match came from their wallet. */
+    fn pulled(env: &Env, player: &Address) -> bool {
+        env.storage()
+            .temporary()
+            .has(&UserData::Pulled(player.clone()))
+    }
+
+    /** Gives back a stake, to wherever it came from. */
+    fn refund(env: &Env, player: &Address, amount: i128) {
+        if Self::pulled(env, player) {
+            let contract = env.current_contract_address();
+            let client = token::Client::new(env, &Self::token(env));
+            client.transfer(&contract, player, &amount);
+        } else {
+            Self::credit(env, player, amount);
+        }
+    }
+
+    /** Takes `amount` out of a player's balance to hold in the contract. */
+    fn debit(env: &Env, player: &Address, amount: i128) {
+        let mut balance: i128 = Self::balance(env, player.clone());
+        if balance < amount {
+            panic_with_error!(env, Error::TooPoor); // too poor to play
+        }
+        balance -= amount;
 
         let store = env.storage().persistent();
         let user = UserData::Balance(player.clone());
         store.set(&user, &balance);
     }
 
-    fn get_fee() -> i128 {
-        COST_TO_PLAY / 100 // 1%
+    /** Returns `amount` held in the contract to a player's balance. */
+    fn credit(env: &Env, player: &Address, amount: i128) {
+        let balance = Self::balance(env, player.clone()).max(0) + amount;
+
+        persist(env, &UserData::Balance(player.clone()), &balance);
+    }
+
+    fn get_fee(stake: i128) -> i128 {
+        stake / 100 // 1%
+    }
+
+    /**
+     * What the winner takes home from their `stake` and the loser's `lost`,
+     * once the fee on each has been charged (whenever the policy charges it).
+     */
+    fn get_payout(stake: i128, lost: i128) -> i128 {
+        stake + lost - Self::get_fee(stake) - Self::get_fee(lost)
+    }
+
+    /** Returns what `player` has at stake in their match against `opp`. */
+    fn held(env: &Env, player: &Address, opp: &Address) -> i128 {
+        if house::is(env, player) {
+            return house::stake(env, opp);
+        }
+        env.storage()
+            .temporary()
+            .get(&UserData::Stake(player.clone()))
+            .unwrap_or(0)
+    }
+
+    /** Returns the score `player` has banked in their match against `opp`. */
+    fn banked(env: &Env, player: &Address, opp: &Address) -> u32 {
+        if house::is(env, player) {
+            return house::score(env, opp);
+        }
+        env.storage()
+            .temporary()
+            .get(&UserData::Score(player.clone()))
+            .unwrap_or(0)
+    }
+
+    /**
+     * Sets aside the reward token for a finished match. Both players get
+     * rewards for participation (so millenial coded..), with the winner's
+     * multiplied up as the reward schedule says.
+     */
+    fn send_rewards(env: &Env, winner: &Address, loser: &Address) {
+        // Unwagered games would be free to farm.
+        if Self::practicing(env, winner) || bot::is(env, winner) || bot::is(env, loser) {
+            return;
+        }
+
+        // A series is wagered once, so it earns once, when it's decided.
+        if series::current(env, winner).is_some() {
+            return;
+        }
+
+        // They're only sent out when claimed (see `rewards` module).
+        let store = env.storage().temporary();
+        let started = store
+            .get(&UserData::Started(winner.clone()))
+            .or_else(|| store.get(&UserData::Started(loser.clone())));
+        rewards::emit(env, winner, loser, started);
     }
 
-    fn get_payout() -> i128 {
-        COST_TO_PLAY * 2 - Self::get_fee()
+    /** Picks the dice at the indices in `save` out of `roll`. */
+    fn saved_dice(env: &Env, roll: &Vec<u32>, save: &Vec<u32>) -> Vec<u32> {
+        if save.len() > roll.len() {
+            panic_with_error!(env, Error::BadDieHold)
+        }
+
+        let mut saved: Vec<u32> = Vec::new(env);
+        for (i, dice_idx) in save.iter().enumerate() {
+            // Each die can only be kept once.
+            if save.slice(..i as u32).contains(dice_idx) {
+                panic_with_error!(env, Error::BadDieHold);
+            }
+            saved.push_back(match roll.get(dice_idx) {
+                Some(x) => x,
+                None => panic_with_error!(env, Error::BadDieHold),
+            });
+        }
+        saved
+    }
+
+    /** Rolls `count` fresh dice. */
+    fn roll_dice(env: &Env, count: u32) -> Vec<u32> {
+        let mut roll = Vec::new(env);
+        for _i in 1..=count {
+            let die: u64 = env.prng().gen_range(1..=6);
+            roll.push_back(die as u32);
+        }
+        roll
+    }
+
+    /** Returns the score `player` needs to win their current match. */
+    fn target(env: &Env, player: &Address) -> u32 {
+        let terms: Option<MatchTerms> = env
+            .storage()
+            .temporary()
+            .get(&UserData::Terms(player.clone()));
+        terms.map_or(WIN_THRESHOLD, |terms| terms.target_a)
     }
 
     fn score_turn(env: &Env, dice: &Vec<u32>, enforce: bool) -> u32 {
@@ -709,7 +1704,7 @@ impl Farkle {
         // 1 2 3 4 5 6 - 1500 points
 
         // First, group the dice by number.
-        let mut groups = Map::new(&env);
+        let mut groups = Map::new(env);
         for die in dice.into_iter() {
             groups.set(die, groups.get(die).unwrap_or(0) + 1);
         }
@@ -806,44 +1801,121 @@ impl Farkle {
             b: b.clone(),
             first: first_player.clone(),
         }
-        .publish(&env);
+        .publish(env);
 
         first_player
     }
 
     fn emit_roll(env: &Env, player: Address, roll: &Vec<u32>) {
         RollEvent {
-            player: player,
+            player,
             roll: roll.clone(),
         }
-        .publish(&env);
+        .publish(env);
     }
 
     fn emit_bust(env: &Env, player: Address, roll: &Vec<u32>) {
         BustEvent {
-            player: player,
+            player,
             roll: roll.clone(),
         }
-        .publish(&env);
+        .publish(env);
     }
 
     fn emit_reroll(env: &Env, player: &Address, dice: &Vec<u32>, score: u32, stop: bool) {
         RerollEvent {
             player: player.clone(),
             dice: dice.clone(),
-            score: score.into(),
-            stop: stop,
+            score,
+            stop,
+        }
+        .publish(env);
+    }
+
+    fn emit_deposit(env: &Env, player: &Address, amount: i128, balance: i128) {
+        DepositEvent {
+            player: player.clone(),
+            amount,
+            balance,
         }
-        .publish(&env);
+        .publish(env);
     }
 
-    fn emit_win(env: &Env, winner: &Address, score: u32) {
+    fn emit_withdraw(env: &Env, player: &Address, to: &Address, amount: i128, balance: i128) {
+        WithdrawEvent {
+            player: player.clone(),
+            to: to.clone(),
+            amount,
+            balance,
+        }
+        .publish(env);
+    }
+
+    fn emit_win(env: &Env, winner: &Address, score: u32, gain: i32, loss: i32) {
         WinEvent {
             winner: winner.clone(),
-            score: score,
+            score,
+            winner_rating: gain,
+            loser_rating: loss,
         }
-        .publish(&env);
+        .publish(env);
     }
 }
 
+/**
+ * Writes a value to persistent storage, keeping it alive for about a week
+ * past its last write.
+ */
+fn persist<K, V>(env: &Env, key: &K, value: &V)
+where
+    K: IntoVal<Env, Val>,
+    V: IntoVal<Env, Val>,
+{
+    let store = env.storage().persistent();
+    store.set(key, value);
+    store.extend_ttl(
+        key,
+        60 * 60 * 24,         /* if < 1 day */
+        60 * 60 * 24 * 7 / 5, /* ~7 days */
+    );
+}
+
+mod bot;
+mod contest;
+mod cube;
+mod daily;
+mod fees;
+mod head_to_head;
+mod history;
+mod house;
+mod jackpot;
+mod league;
+mod rating;
+mod referral;
+mod rewards;
+mod series;
+mod solo;
+mod staking;
+mod stats;
 mod test;
+mod tournament;
+
+pub use bot::{BotState, FarkleStrategy, FarkleStrategyClient};
+pub use contest::{Contest, ContestEntry, ContestPrizeEvent, ContestScoreEvent, Standing};
+pub use cube::{Cube, DoubleEvent};
+pub use daily::{DailyEntry, DailyScoreEvent};
+pub use fees::{FeePolicy, FeeShare, FeesCollectedEvent, MatchFee};
+pub use head_to_head::HeadToHead;
+pub use history::MatchResult;
+pub use house::Bankroll;
+pub use jackpot::{JackpotConfig, JackpotEvent, JackpotPattern, JackpotTrigger};
+pub use league::{Fixture, FixtureEvent, LeagueStanding, Season};
+pub use referral::Referrals;
+pub use rewards::{RewardEpoch, RewardGuard, RewardSchedule, RewardWithheldEvent, Withheld};
+pub use series::{Series, SeriesGameEvent, SeriesWinEvent};
+pub use solo::Run;
+pub use staking::{StakeEvent, Staker, StakingPool};
+pub use stats::PlayerStats;
+pub use tournament::{
+    Pairing, Tournament, TournamentPrizeEvent, TournamentRoundEvent, TournamentStatus,
+};
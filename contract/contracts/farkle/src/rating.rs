@@ -0,0 +1,70 @@
+//! Elo skill ratings, kept per address across matches.
+//!
+//! Soroban has no floating point, so the expected-score curve
+//! `1 / (1 + 10^(-gap / 400))` is precomputed in per-mille steps of 25 rating
+//! points and linearly interpolated between them.
+
+use soroban_sdk::{Address, Env};
+
+use crate::{persist, UserData};
+
+pub const DEFAULT_RATING: u32 = 1200;
+const MIN_RATING: u32 = 100;
+const K_FACTOR: u32 = 32;
+
+const STEP: u32 = 25;
+const EXPECTED: [u32; 33] = [
+    500, 536, 571, 606, 640, 673, 703, 733, 760, 785, 808, 830, 849, 867, 882, 896, 909, 920, 930,
+    939, 947, 954, 960, 965, 969, 973, 977, 980, 983, 985, 987, 989, 990,
+];
+
+/** Returns the rating on record for `player`, or the default for newcomers. */
+pub fn get(env: &Env, player: &Address) -> u32 {
+    env.storage()
+        .persistent()
+        .get(&UserData::Rating(player.clone()))
+        .unwrap_or(DEFAULT_RATING)
+}
+
+/**
+ * Moves both ratings after `winner` beats `loser`.
+ *
+ * # Returns
+ *
+ * The signed rating change for the winner and the loser, respectively.
+ */
+pub fn record_win(env: &Env, winner: &Address, loser: &Address) -> (i32, i32) {
+    let w = get(env, winner);
+    let l = get(env, loser);
+
+    // How many points (out of 1000) the winner was expected to take.
+    let expected = if w >= l {
+        expected(w - l)
+    } else {
+        1000 - expected(l - w)
+    };
+
+    // Always move by at least a point, so even a heavy favorite gains something.
+    let gain = ((K_FACTOR * (1000 - expected) + 500) / 1000).max(1);
+    let loss = gain.min(l.saturating_sub(MIN_RATING));
+
+    set(env, winner, w + gain);
+    set(env, loser, l - loss);
+
+    (gain as i32, -(loss as i32))
+}
+
+/** Expected score (per mille) for the favorite of a `gap`-point mismatch. */
+fn expected(gap: u32) -> u32 {
+    let i = (gap / STEP) as usize;
+    if i + 1 >= EXPECTED.len() {
+        return EXPECTED[EXPECTED.len() - 1];
+    }
+
+    let (lo, hi) = (EXPECTED[i], EXPECTED[i + 1]);
+    lo + (hi - lo) * (gap % STEP) / STEP
+}
+
+fn set(env: &Env, player: &Address, rating: u32) {
+    persist(env, &UserData::Rating(player.clone()), &rating);
+}
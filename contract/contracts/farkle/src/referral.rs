@@ -0,0 +1,124 @@
+//! Referrals, which share match fees with whoever brought a player in.
+//!
+//! A player names their referrer once. From then on, a share of the fee on
+//! every match stake they put up (set by the admin) is added to the
+//! referrer's balance, from which it can be withdrawn like any other funds.
+
+use soroban_sdk::{contractimpl, contracttype, panic_with_error};
+use soroban_sdk::{Address, Env};
+
+use crate::{
+    fees, jackpot, persist, staking, AdminData, Error, Farkle, FarkleArgs, FarkleClient, UserData,
+};
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct Referrals {
+    pub referred: u32, // how many players named this referrer
+    pub earned: i128,  // fees shared with them, all told
+}
+
+#[contractimpl]
+impl Farkle {
+    /**
+     * Names the player who referred `player`. This can only be done once.
+     *
+     * # Panics
+     *
+     * - If `player` already has a referrer.
+     * - If `referrer` is the player themselves, or the contract.
+     */
+    pub fn set_referrer(env: Env, player: Address, referrer: Address) {
+        Self::check_init(&env);
+
+        let store = env.storage().persistent();
+        let key = UserData::Referrer(player.clone());
+        if store.has(&key) {
+            panic_with_error!(&env, Error::AlreadyReferred);
+        }
+        if referrer == player || referrer == env.current_contract_address() {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        player.require_auth();
+
+        persist(&env, &key, &referrer);
+
+        let mut record = referrals(&env, &referrer);
+        record.referred += 1;
+        persist(&env, &UserData::Referrals(referrer), &record);
+    }
+
+    /** Returns who referred `player`, if anyone. */
+    pub fn referrer(env: Env, player: Address) -> Option<Address> {
+        env.storage().persistent().get(&UserData::Referrer(player))
+    }
+
+    /** Returns how many players a referrer brought in, and what they earned. */
+    pub fn referrals(env: Env, referrer: Address) -> Referrals {
+        referrals(&env, &referrer)
+    }
+
+    /**
+     * Sets the share (per mille) of each match fee that goes to the paying
+     * player's referrer.
+     *
+     * # Panics
+     *
+     * - If the shares of match fees paid out come to more than 1000.
+     */
+    pub fn set_referral_share(env: Env, share: u32) {
+        Self::check_init(&env);
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        fees::check_shares(&env, jackpot::share(&env), share, staking::share(&env));
+        env.storage()
+            .instance()
+            .set(&AdminData::ReferralShare, &share);
+    }
+
+    /** Returns the share (per mille) of match fees paid to referrers. */
+    pub fn referral_share(env: Env) -> u32 {
+        share(&env)
+    }
+}
+
+pub fn share(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&AdminData::ReferralShare)
+        .unwrap_or(0)
+}
+
+/** Pays `player`'s referrer their share of `fee`, returning what was paid. */
+pub fn pay(env: &Env, player: &Address, fee: i128) -> i128 {
+    let referrer: Address = match env
+        .storage()
+        .persistent()
+        .get(&UserData::Referrer(player.clone()))
+    {
+        Some(referrer) => referrer,
+        None => return 0,
+    };
+
+    let cut = fee * share(env) as i128 / 1000;
+    if cut <= 0 {
+        return 0;
+    }
+
+    Farkle::credit(env, &referrer, cut);
+
+    let mut record = referrals(env, &referrer);
+    record.earned += cut;
+    persist(env, &UserData::Referrals(referrer), &record);
+    cut
+}
+
+fn referrals(env: &Env, referrer: &Address) -> Referrals {
+    env.storage()
+        .persistent()
+        .get(&UserData::Referrals(referrer.clone()))
+        .unwrap_or_default()
+}
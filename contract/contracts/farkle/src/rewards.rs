@@ -0,0 +1,417 @@
+//! Participation rewards, held for players to claim.
+//!
+//! Rewards are added to a ledger in the contract as matches are won, rather
+//! than sent out right away, so that a player whose account can't receive
+//! the reward token never holds up the end of a match. Each player claims
+//! their own whenever they like.
+//!
+//! How much is emitted follows a schedule the admin sets. Each match earns a
+//! base reward, multiplied separately for the winner and the loser, plus a
+//! bonus for a winner on a streak. Emission is capped per epoch (a fixed run
+//! of ledgers), with the cap decaying from one epoch to the next, and each
+//! player can only earn so much in a day. The caps are what keep anyone from
+//! draining the reserve by playing themselves over and over.
+//!
+//! On top of that, the admin can set a guard that withholds a match's
+//! rewards altogether when it looks like farming: the same pair playing
+//! again too soon after a rewarded match, a match won faster than any honest
+//! one would be, or a loser who gives up most of the matches they lose.
+
+use soroban_sdk::{contractevent, contractimpl, contracttype, panic_with_error, token};
+use soroban_sdk::{Address, Env};
+
+use crate::{
+    head_to_head, house, persist, stats, AdminData, Error, Farkle, FarkleArgs, FarkleClient,
+    UserData, REWARD,
+};
+
+const DAY: u64 = 60 * 60 * 24; // seconds
+const SCALE: i128 = 1_000_000_000; // fixed point for compounding the decay
+const MIN_LOSSES: u32 = 5; // before a loser's record says anything
+const MAX_MULTIPLIER: u32 = 10_000; // per mille, for each part of a multiplier
+const MAX_AMOUNT: i128 = 1_000_000_000_000_000_000; // for the base and caps, so sums fit
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct RewardSchedule {
+    pub base: i128,        // reward per match, before multipliers
+    pub winner: u32,       // per mille of `base` the winner earns
+    pub loser: u32,        // per mille of `base` the loser earns
+    pub streak_bonus: u32, // per mille of `base` more per win in a row before this one
+    pub max_streak: u32,   // most wins in a row the bonus counts
+    pub epoch_length: u32, // ledgers per epoch
+    pub epoch_cap: i128,   // most emitted in the first epoch, or 0 for no cap
+    pub decay: u32,        // per mille of its cap each epoch passes on to the next
+    pub daily_cap: i128,   // most a player earns in a (UTC) day, or 0 for no cap
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct RewardEpoch {
+    pub start: u32,    // ledger the schedule took effect on
+    pub epoch: u32,    // counting from zero at `start`
+    pub cap: i128,     // most this epoch emits, if the schedule has a cap
+    pub emitted: i128, // emitted so far this epoch
+}
+
+/** Limits on which matches earn rewards at all. Zero turns a limit off. */
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct RewardGuard {
+    pub pair_cooldown: u32, // ledgers after a rewarded match before the pair earns again
+    pub min_ledgers: u32,   // matches won in fewer ledgers than this earn nothing
+    pub max_give_ups: u32,  // per mille of a loser's losses that may be forfeits or concessions
+}
+
+/** Why a match's rewards were withheld. */
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum Withheld {
+    Cooldown, // the pair played a rewarded match too recently
+    TooFast,  // the match ended in fewer than `min_ledgers`
+    GiveUps,  // the loser forfeits or concedes too many of their losses
+}
+
+#[contractevent(topics = ["reward_withheld"], data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RewardWithheldEvent {
+    #[topic]
+    winner: Address,
+
+    #[topic]
+    loser: Address,
+
+    reason: Withheld,
+}
+
+#[contractimpl]
+impl Farkle {
+    /**
+     * Sends a player the rewards they've earned so far.
+     *
+     * # Returns
+     *
+     * The amount claimed, which may be zero.
+     *
+     * # Panics
+     *
+     * - If the reserve doesn't hold enough to pay them.
+     */
+    pub fn claim_rewards(env: Env, player: Address) -> i128 {
+        Self::check_init(&env);
+
+        let amount = pending(&env, &player);
+        if amount > 0 {
+            player.require_auth();
+
+            // Staked tokens aren't part of the reserve.
+            let contract = env.current_contract_address();
+            let reward: Address = env.storage().instance().get(&AdminData::Reward).unwrap();
+            let client = token::Client::new(&env, &reward);
+            if client.balance(&contract) - Self::staking_pool(env.clone()).staked < amount {
+                panic_with_error!(&env, Error::RewardsExhausted);
+            }
+            client.transfer(&contract, &player, &amount);
+
+            env.storage()
+                .persistent()
+                .remove(&UserData::Rewards(player));
+        }
+
+        amount
+    }
+
+    /** Returns the rewards a player has earned but not yet claimed. */
+    pub fn pending_rewards(env: Env, player: Address) -> i128 {
+        pending(&env, &player)
+    }
+
+    /**
+     * Sets the reward schedule, starting its first epoch now.
+     *
+     * # Panics
+     *
+     * - If any amount is negative, or the decay is above 1000.
+     * - If the base or either cap is above 10^18.
+     * - If the winner's or loser's multiplier, the streak bonus, or the most
+     *   the streak bonus can add up to (`max_streak` times over) is above
+     *   10,000.
+     * - If there's an epoch cap, but epochs have no length.
+     */
+    pub fn set_reward_schedule(env: Env, schedule: RewardSchedule) {
+        Self::check_init(&env);
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        if !(0..=MAX_AMOUNT).contains(&schedule.base)
+            || !(0..=MAX_AMOUNT).contains(&schedule.epoch_cap)
+            || !(0..=MAX_AMOUNT).contains(&schedule.daily_cap)
+            || schedule.decay > 1000
+            || schedule.winner > MAX_MULTIPLIER
+            || schedule.loser > MAX_MULTIPLIER
+            || schedule.streak_bonus > MAX_MULTIPLIER
+            || schedule.max_streak as u64 * schedule.streak_bonus as u64 > MAX_MULTIPLIER as u64
+            || (schedule.epoch_cap > 0 && schedule.epoch_length == 0)
+        {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        let instance = env.storage().instance();
+        instance.set(&AdminData::RewardSchedule, &schedule);
+        instance.set(
+            &AdminData::RewardEpoch,
+            &RewardEpoch {
+                start: env.ledger().sequence(),
+                epoch: 0,
+                cap: schedule.epoch_cap,
+                emitted: 0,
+            },
+        );
+    }
+
+    /** Returns the reward schedule. */
+    pub fn reward_schedule(env: Env) -> RewardSchedule {
+        schedule(&env)
+    }
+
+    /** Sets the guard against reward farming. */
+    pub fn set_reward_guard(env: Env, guard: RewardGuard) {
+        Self::check_init(&env);
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        env.storage()
+            .instance()
+            .set(&AdminData::RewardGuard, &guard);
+    }
+
+    /** Returns the guard against reward farming. */
+    pub fn reward_guard(env: Env) -> RewardGuard {
+        guard(&env)
+    }
+
+    /**
+     * Returns the ledger until which two players earn no rewards against
+     * each other, or zero if they never have.
+     */
+    pub fn reward_cooldown(env: Env, a: Address, b: Address) -> u32 {
+        cooldown(&env, &a, &b)
+    }
+
+    /** Returns the current reward epoch, and what's been emitted in it. */
+    pub fn reward_epoch(env: Env) -> RewardEpoch {
+        epoch(&env, &schedule(&env))
+    }
+}
+
+/**
+ * Emits the rewards for a match `winner` won against `loser`, as far as
+ * the caps allow. The winner's are emitted first. `started` is the ledger
+ * the match (or the series it decided) began on, if it's known.
+ */
+pub fn emit(env: &Env, winner: &Address, loser: &Address, started: Option<u32>) {
+    let guard = guard(env);
+    if let Some(reason) = suspicious(env, &guard, winner, loser, started) {
+        RewardWithheldEvent {
+            winner: winner.clone(),
+            loser: loser.clone(),
+            reason,
+        }
+        .publish(env);
+        return;
+    }
+
+    if guard.pair_cooldown > 0 {
+        let until = env.ledger().sequence() + guard.pair_cooldown;
+        let (a, b) = head_to_head::sorted(winner, loser);
+        let key = UserData::Cooldown(a.clone(), b.clone());
+        persist(env, &key, &until);
+    }
+
+    let schedule = schedule(env);
+    let mut epoch = epoch(env, &schedule);
+
+    // This win is the one after the streak so far.
+    let streak = stats::get(env, winner).streak.min(schedule.max_streak);
+    let multipliers = [
+        (winner, schedule.winner + streak * schedule.streak_bonus),
+        (loser, schedule.loser),
+    ];
+
+    for (player, multiplier) in multipliers {
+        if house::is(env, player) {
+            continue;
+        }
+
+        let mut amount = schedule.base * multiplier as i128 / 1000;
+        if schedule.epoch_cap > 0 {
+            amount = amount.min(epoch.cap - epoch.emitted);
+        }
+        amount = amount.min(daily_room(env, &schedule, player));
+        if amount <= 0 {
+            continue;
+        }
+
+        epoch.emitted += amount;
+        accrue(env, player, amount);
+        earned_today(env, player, amount);
+    }
+
+    env.storage()
+        .instance()
+        .set(&AdminData::RewardEpoch, &epoch);
+}
+
+/** Returns why the match `winner` just won looks like farming, if it does. */
+fn suspicious(
+    env: &Env,
+    guard: &RewardGuard,
+    winner: &Address,
+    loser: &Address,
+    started: Option<u32>,
+) -> Option<Withheld> {
+    let now = env.ledger().sequence();
+    if now < cooldown(env, winner, loser) {
+        return Some(Withheld::Cooldown);
+    }
+
+    if let Some(started) = started {
+        if now - started < guard.min_ledgers {
+            return Some(Withheld::TooFast);
+        }
+    }
+
+    let record = stats::get(env, loser);
+    let give_ups = (record.forfeited + record.conceded) as u64 * 1000;
+    if guard.max_give_ups > 0
+        && record.lost >= MIN_LOSSES
+        && give_ups > guard.max_give_ups as u64 * record.lost as u64
+    {
+        return Some(Withheld::GiveUps);
+    }
+
+    None
+}
+
+fn guard(env: &Env) -> RewardGuard {
+    env.storage()
+        .instance()
+        .get(&AdminData::RewardGuard)
+        .unwrap_or_default()
+}
+
+fn cooldown(env: &Env, a: &Address, b: &Address) -> u32 {
+    let (a, b) = head_to_head::sorted(a, b);
+    env.storage()
+        .persistent()
+        .get(&UserData::Cooldown(a.clone(), b.clone()))
+        .unwrap_or(0)
+}
+
+/** Adds `amount` to the rewards `player` can claim. */
+pub fn accrue(env: &Env, player: &Address, amount: i128) {
+    let key = UserData::Rewards(player.clone());
+    persist(env, &key, &(pending(env, player) + amount));
+}
+
+fn pending(env: &Env, player: &Address) -> i128 {
+    env.storage()
+        .persistent()
+        .get(&UserData::Rewards(player.clone()))
+        .unwrap_or(0)
+}
+
+fn schedule(env: &Env) -> RewardSchedule {
+    env.storage()
+        .instance()
+        .get(&AdminData::RewardSchedule)
+        .unwrap_or(RewardSchedule {
+            base: REWARD,
+            winner: 2000,
+            loser: 1000,
+            streak_bonus: 0,
+            max_streak: 0,
+            epoch_length: 0,
+            epoch_cap: 0,
+            decay: 1000,
+            daily_cap: 0,
+        })
+}
+
+/** Returns the epoch as of now, moving on from the stored one if it's over. */
+fn epoch(env: &Env, schedule: &RewardSchedule) -> RewardEpoch {
+    let last: RewardEpoch = env
+        .storage()
+        .instance()
+        .get(&AdminData::RewardEpoch)
+        .unwrap_or(RewardEpoch {
+            start: 0,
+            epoch: 0,
+            cap: 0,
+            emitted: 0,
+        });
+    if schedule.epoch_cap == 0 {
+        return last;
+    }
+
+    let now = (env.ledger().sequence() - last.start) / schedule.epoch_length;
+    if now == last.epoch {
+        return last;
+    }
+
+    RewardEpoch {
+        start: last.start,
+        epoch: now,
+        cap: decayed(schedule.epoch_cap, schedule.decay, now),
+        emitted: 0,
+    }
+}
+
+/** Returns `cap` after `epochs` rounds of keeping `decay` per mille of it. */
+fn decayed(cap: i128, decay: u32, epochs: u32) -> i128 {
+    let mut cap = cap * SCALE;
+    let mut factor = decay as i128 * SCALE / 1000;
+    let mut n = epochs;
+    while n > 0 && cap > 0 {
+        if n & 1 == 1 {
+            cap = cap * factor / SCALE;
+        }
+        factor = factor * factor / SCALE;
+        n >>= 1;
+    }
+    cap / SCALE
+}
+
+/** Returns how much more `player` can earn today. */
+fn daily_room(env: &Env, schedule: &RewardSchedule, player: &Address) -> i128 {
+    if schedule.daily_cap == 0 {
+        return i128::MAX;
+    }
+
+    let (day, earned): (u64, i128) = env
+        .storage()
+        .persistent()
+        .get(&UserData::RewardDay(player.clone()))
+        .unwrap_or((0, 0));
+    if day == env.ledger().timestamp() / DAY {
+        schedule.daily_cap - earned
+    } else {
+        schedule.daily_cap
+    }
+}
+
+fn earned_today(env: &Env, player: &Address, amount: i128) {
+    let store = env.storage().persistent();
+    let key = UserData::RewardDay(player.clone());
+    let today = env.ledger().timestamp() / DAY;
+
+    let (day, earned): (u64, i128) = store.get(&key).unwrap_or((today, 0));
+    let earned = if day == today {
+        earned + amount
+    } else {
+        amount
+    };
+    persist(env, &key, &(today, earned));
+}
@@ -0,0 +1,267 @@
+//! Best-of-N series between two players.
+//!
+//! The wager is held once, when the series starts, and each game is then an
+//! ordinary match played through `roll`. Games are unwagered on their own:
+//! only the player who takes the majority of them collects the pot.
+
+use soroban_sdk::{contractevent, contractimpl, contracttype, panic_with_error, token};
+use soroban_sdk::{Address, Env};
+
+use crate::fees::{self, FeePolicy};
+use crate::{
+    persist, rewards, stats, AdminData, Error, Farkle, FarkleArgs, FarkleClient, MatchTerms,
+    SeriesData, UserData, COST_TO_PLAY,
+};
+
+const MAX_GAMES: u32 = 9;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Series {
+    pub id: u64,
+    pub a: Address,
+    pub b: Address,
+    pub games: u32, // best of how many
+    pub a_wins: u32,
+    pub b_wins: u32,
+    pub stake: i128,       // held from each player
+    pub policy: FeePolicy, // when the fee on the stakes is charged
+    pub a_started: bool,   // whether `a` went first in the opening game
+    pub started: u32,      // ledger number the opening game began on
+    pub over: bool,
+    pub winner: Option<Address>, // none while playing, or if it was aborted
+}
+
+#[contractevent(topics = ["series_game"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SeriesGameEvent {
+    #[topic]
+    id: u64,
+
+    winner: Address,
+    a_wins: u32,
+    b_wins: u32,
+}
+
+#[contractevent(topics = ["series_win"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SeriesWinEvent {
+    #[topic]
+    id: u64,
+
+    #[topic]
+    winner: Address,
+
+    a_wins: u32,
+    b_wins: u32,
+}
+
+#[contractimpl]
+impl Farkle {
+    /**
+     * Begins a best-of-`games` series between two players.
+     *
+     * This works like `engage`, except the wager covers the whole series. The
+     * first game starts right away, and each one after it starts as soon as
+     * the last one ends, with the players taking turns going first.
+     *
+     * # Arguments
+     *
+     * `a` - The player on one side of the series
+     * `b` - The player on the other side of the series
+     * `games` - How many games the series is the best of (3, 5, 7 or 9)
+     *
+     * # Returns
+     *
+     * The identifier of the series, for `series_state`.
+     *
+     * # Panics
+     *
+     * - If `games` isn't an odd number between 3 and 9.
+     * - If either player is already in a game.
+     * - If a player doesn't have a sufficient amount deposited to wager.
+     */
+    pub fn engage_series(env: Env, a: Address, b: Address, games: u32) -> u64 {
+        Self::check_init(&env);
+        if !(3..=MAX_GAMES).contains(&games) || games.is_multiple_of(2) {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        Self::check_free(&env, &a);
+        Self::check_free(&env, &b);
+
+        a.require_auth();
+        b.require_auth();
+
+        Self::debit(&env, &a, COST_TO_PLAY);
+        Self::debit(&env, &b, COST_TO_PLAY);
+
+        let policy = fees::policy(&env);
+        if policy == FeePolicy::OnEngage {
+            fees::accrue_match(&env, &a, Self::get_fee(COST_TO_PLAY));
+            fees::accrue_match(&env, &b, Self::get_fee(COST_TO_PLAY));
+        }
+
+        let instance = env.storage().instance();
+        let id: u64 = instance.get(&SeriesData::Count).unwrap_or(0);
+        instance.set(&SeriesData::Count, &(id + 1));
+
+        let first: u64 = env.prng().gen_range(1..=2);
+        let series = Series {
+            id,
+            a: a.clone(),
+            b: b.clone(),
+            games,
+            a_wins: 0,
+            b_wins: 0,
+            stake: COST_TO_PLAY,
+            policy,
+            a_started: first == 1,
+            started: env.ledger().sequence(),
+            over: false,
+            winner: None,
+        };
+        set(&env, &series);
+        start_game(&env, &series, series.a_started);
+
+        id
+    }
+
+    /** Returns the state of a series, as started by `engage_series`. */
+    pub fn series_state(env: Env, id: u64) -> Series {
+        match env.storage().persistent().get(&SeriesData::Series(id)) {
+            Some(series) => series,
+            None => panic_with_error!(&env, Error::WrongMatch),
+        }
+    }
+
+    /**
+     * Calls off a series whose current game has lapsed, giving back the
+     * stakes (less any fee already charged on them). Only the admin may do
+     * this.
+     *
+     * A game lapses when nobody touches it for long enough that the ledger
+     * drops it, and `end_match` can no longer find the series through it.
+     *
+     * # Panics
+     *
+     * - If the series is over.
+     * - If a game of the series is still underway (see `end_match`).
+     */
+    pub fn abort_series(env: Env, id: u64) {
+        Self::check_init(&env);
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        let series = Self::series_state(env.clone(), id);
+        if series.over {
+            panic_with_error!(&env, Error::WrongMatch);
+        }
+        if current(&env, &series.a) == Some(id) || current(&env, &series.b) == Some(id) {
+            panic_with_error!(&env, Error::AlreadyPlaying);
+        }
+        abort(&env, id);
+    }
+}
+
+/** Returns the series `player` is currently playing a game of, if any. */
+pub fn current(env: &Env, player: &Address) -> Option<u64> {
+    env.storage()
+        .temporary()
+        .get(&UserData::Series(player.clone()))
+}
+
+/**
+ * Records the end of a game in series `id`, which has already been closed out
+ * as a match, then either starts the next game or pays out the series.
+ */
+pub fn record_game(env: &Env, id: u64, winner: &Address, loser: &Address) {
+    let mut series = Farkle::series_state(env.clone(), id);
+    if *winner == series.a {
+        series.a_wins += 1;
+    } else {
+        series.b_wins += 1;
+    }
+
+    SeriesGameEvent {
+        id,
+        winner: winner.clone(),
+        a_wins: series.a_wins,
+        b_wins: series.b_wins,
+    }
+    .publish(env);
+
+    if series.a_wins.max(series.b_wins) <= series.games / 2 {
+        // The first player alternates from game to game.
+        let played = series.a_wins + series.b_wins;
+        set(env, &series);
+        start_game(env, &series, series.a_started == played.is_multiple_of(2));
+        return;
+    }
+
+    series.over = true;
+    series.winner = Some(winner.clone());
+    set(env, &series);
+    unlink(env, &series);
+
+    if series.policy == FeePolicy::OnSettle {
+        fees::accrue_match(env, &series.a, Farkle::get_fee(series.stake));
+        fees::accrue_match(env, &series.b, Farkle::get_fee(series.stake));
+    }
+    let payout = Farkle::get_payout(series.stake, series.stake);
+    let client = token::Client::new(env, &Farkle::token(env));
+    client.transfer(&env.current_contract_address(), winner, &payout);
+
+    stats::record_wager(env, winner, series.stake, payout);
+    stats::record_wager(env, loser, series.stake, 0);
+    rewards::emit(env, winner, loser, Some(series.started));
+
+    SeriesWinEvent {
+        id,
+        winner: winner.clone(),
+        a_wins: series.a_wins,
+        b_wins: series.b_wins,
+    }
+    .publish(env);
+}
+
+/**
+ * Marks series `id` as over without a winner, e.g. when the admin steps in,
+ * and returns the stakes (less any fee already charged on them).
+ */
+pub fn abort(env: &Env, id: u64) {
+    let mut series = Farkle::series_state(env.clone(), id);
+    series.over = true;
+    set(env, &series);
+    unlink(env, &series);
+
+    let mut refund = series.stake;
+    if series.policy == FeePolicy::OnEngage {
+        refund -= Farkle::get_fee(series.stake);
+    }
+    Farkle::credit(env, &series.a, refund);
+    Farkle::credit(env, &series.b, refund);
+}
+
+fn start_game(env: &Env, series: &Series, a_first: bool) {
+    let store = env.storage().temporary();
+    store.set(&UserData::Series(series.a.clone()), &series.id);
+    store.set(&UserData::Series(series.b.clone()), &series.id);
+
+    Farkle::start_match(env, &series.a, &series.b, a_first, &MatchTerms::even(0));
+}
+
+fn unlink(env: &Env, series: &Series) {
+    let store = env.storage().temporary();
+    for player in [&series.a, &series.b] {
+        let key = UserData::Series(player.clone());
+        if store.has(&key) {
+            store.remove(&key);
+        }
+    }
+}
+
+fn set(env: &Env, series: &Series) {
+    persist(env, &SeriesData::Series(series.id), series);
+}
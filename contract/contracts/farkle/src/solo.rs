@@ -0,0 +1,81 @@
+//! Turns played alone, for modes where there's no opponent to pass to.
+//!
+//! A `Run` follows the same rules as `roll` does in a match: the same dice
+//! may be kept, they score the same, and a bust loses the turn's points. The
+//! only difference is that ending a turn just starts the player's next one.
+
+use soroban_sdk::{contracttype, panic_with_error, Env, Vec};
+
+use crate::{Error, Farkle};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Run {
+    pub score: u32,      // points banked so far
+    pub turn_score: u32, // points set aside this turn
+    pub turns: u32,      // turns finished, whether banked or busted
+    pub dice: Vec<u32>,  // the last roll, or empty at the start of a turn
+}
+
+impl Run {
+    pub fn new(env: &Env) -> Run {
+        Run {
+            score: 0,
+            turn_score: 0,
+            turns: 0,
+            dice: Vec::new(env),
+        }
+    }
+}
+
+/**
+ * Plays one `roll` of a run, taking fresh dice from `dice`.
+ *
+ * Arguments and the returned roll work just like `Farkle::roll`'s.
+ */
+pub fn step<F>(env: &Env, run: &mut Run, save: Vec<u32>, stop: bool, mut dice: F) -> Vec<u32>
+where
+    F: FnMut(u32) -> Vec<u32>,
+{
+    let mut count = 6;
+
+    if !run.dice.is_empty() {
+        if save.is_empty() && !stop {
+            panic_with_error!(env, Error::BadDieHold);
+        }
+
+        let saved = Farkle::saved_dice(env, &run.dice, &save);
+        let points = Farkle::score_turn(env, &saved, true);
+        if points == 0 && !stop {
+            panic_with_error!(env, Error::BadDieHold);
+        }
+        run.turn_score += points;
+
+        if stop {
+            run.score += run.turn_score;
+            end_turn(env, run);
+            return Vec::new(env);
+        }
+
+        count = run.dice.len() - saved.len();
+        if count == 0 {
+            count = 6; // hot dice
+        }
+    } else if !save.is_empty() {
+        panic_with_error!(env, Error::BadDieHold);
+    }
+
+    let roll = dice(count);
+    if Farkle::score_turn(env, &roll, false) == 0 {
+        end_turn(env, run);
+    } else {
+        run.dice = roll.clone();
+    }
+    roll
+}
+
+fn end_turn(env: &Env, run: &mut Run) {
+    run.turn_score = 0;
+    run.turns += 1;
+    run.dice = Vec::new(env);
+}
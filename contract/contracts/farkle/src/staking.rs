@@ -0,0 +1,228 @@
+//! Staking the reward token for a share of match fees.
+//!
+//! Holders stake the reward token in the contract, and a share of every match
+//! fee (set by the admin) is split between them in proportion to what they
+//! have staked at the time. Fees earned this way are kept track of with a
+//! running total of fees per staked token, so that paying stakers never
+//! means going through all of them. Whatever doesn't divide evenly is carried
+//! over rather than dropped, so every stroop of the stakers' share is owed to
+//! someone in the end.
+//!
+//! While nothing is staked, the stakers' share stays in the fee vault.
+
+use soroban_sdk::{contractevent, contractimpl, contracttype, panic_with_error, token};
+use soroban_sdk::{Address, Env};
+
+use crate::{
+    fees, jackpot, persist, referral, AdminData, Error, Farkle, FarkleArgs, FarkleClient, UserData,
+};
+
+pub const SCALE: i128 = 1_000_000_000_000; // fixed point for fees per staked token
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct StakingPool {
+    pub share: u32,      // per mille of each match fee paid to stakers
+    pub staked: i128,    // reward tokens staked, all told
+    pub per_token: i128, // fees earned per staked token so far, times 10^12
+    pub carry: i128,     // fees left over from splitting them, times 10^12
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct Staker {
+    pub staked: i128,
+    pub owed: i128,      // fees earned and not yet claimed
+    pub per_token: i128, // the pool's `per_token` when `owed` was last brought up to date
+    pub carry: i128,     // fees earned short of a whole stroop, times 10^12
+}
+
+#[contractevent(topics = ["stake"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StakeEvent {
+    #[topic]
+    staker: Address,
+
+    amount: i128, // negative when unstaking
+    staked: i128,
+}
+
+#[contractimpl]
+impl Farkle {
+    /**
+     * Stakes some of the reward token, to earn a share of match fees.
+     *
+     * # Returns
+     *
+     * The staker's position afterwards.
+     */
+    pub fn stake(env: Env, staker: Address, amount: i128) -> Staker {
+        Self::check_init(&env);
+        if amount <= 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        staker.require_auth();
+
+        let contract = env.current_contract_address();
+        reward_token(&env).transfer(&staker, &contract, &amount);
+
+        let mut pool = pool(&env);
+        let mut position = position(&env, &pool, &staker);
+        position.staked += amount;
+        pool.staked += amount;
+        save(&env, &pool, &staker, &position, amount)
+    }
+
+    /**
+     * Takes staked reward tokens back out. Fees earned on them so far stay
+     * owed until claimed.
+     *
+     * # Panics
+     *
+     * - If the staker doesn't have `amount` staked.
+     */
+    pub fn unstake(env: Env, staker: Address, amount: i128) -> Staker {
+        Self::check_init(&env);
+
+        let mut pool = pool(&env);
+        let mut position = position(&env, &pool, &staker);
+        if amount <= 0 || amount > position.staked {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        staker.require_auth();
+
+        let contract = env.current_contract_address();
+        reward_token(&env).transfer(&contract, &staker, &amount);
+
+        position.staked -= amount;
+        pool.staked -= amount;
+        save(&env, &pool, &staker, &position, -amount)
+    }
+
+    /**
+     * Sends a staker the fees they've earned so far.
+     *
+     * # Returns
+     *
+     * The amount claimed, which may be zero.
+     */
+    pub fn claim(env: Env, staker: Address) -> i128 {
+        Self::check_init(&env);
+
+        let pool = pool(&env);
+        let mut position = position(&env, &pool, &staker);
+        let amount = position.owed;
+        if amount > 0 {
+            staker.require_auth();
+
+            let contract = env.current_contract_address();
+            token::Client::new(&env, &Self::token(&env)).transfer(&contract, &staker, &amount);
+
+            position.owed = 0;
+            set(&env, &staker, &position);
+        }
+
+        amount
+    }
+
+    /** Returns a staker's position, including the fees they're owed. */
+    pub fn staker(env: Env, staker: Address) -> Staker {
+        position(&env, &pool(&env), &staker)
+    }
+
+    /** Returns the state of the staking pool. */
+    pub fn staking_pool(env: Env) -> StakingPool {
+        pool(&env)
+    }
+
+    /**
+     * Sets the share (per mille) of each match fee paid to stakers.
+     *
+     * # Panics
+     *
+     * - If the shares of match fees paid out come to more than 1000.
+     */
+    pub fn set_staking_share(env: Env, share: u32) {
+        Self::check_init(&env);
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        fees::check_shares(&env, jackpot::share(&env), referral::share(&env), share);
+
+        let mut pool = pool(&env);
+        pool.share = share;
+        env.storage().instance().set(&AdminData::Staking, &pool);
+    }
+}
+
+pub fn share(env: &Env) -> u32 {
+    pool(env).share
+}
+
+/** Splits the stakers' share of a match `fee` between them, returning it. */
+pub fn fund(env: &Env, fee: i128) -> i128 {
+    let mut pool = pool(env);
+    let cut = fee * pool.share as i128 / 1000;
+    if cut <= 0 || pool.staked == 0 {
+        return 0;
+    }
+
+    let total = cut * SCALE + pool.carry;
+    pool.per_token += total / pool.staked;
+    pool.carry = total % pool.staked;
+    env.storage().instance().set(&AdminData::Staking, &pool);
+    cut
+}
+
+fn pool(env: &Env) -> StakingPool {
+    env.storage()
+        .instance()
+        .get(&AdminData::Staking)
+        .unwrap_or_default()
+}
+
+/** Returns the staker's position, with what they're owed brought up to date. */
+fn position(env: &Env, pool: &StakingPool, staker: &Address) -> Staker {
+    let mut position: Staker = env
+        .storage()
+        .persistent()
+        .get(&UserData::Staker(staker.clone()))
+        .unwrap_or_default();
+
+    let earned = position.staked * (pool.per_token - position.per_token) + position.carry;
+    position.owed += earned / SCALE;
+    position.carry = earned % SCALE;
+    position.per_token = pool.per_token;
+    position
+}
+
+fn save(
+    env: &Env,
+    pool: &StakingPool,
+    staker: &Address,
+    position: &Staker,
+    amount: i128,
+) -> Staker {
+    env.storage().instance().set(&AdminData::Staking, pool);
+    set(env, staker, position);
+
+    StakeEvent {
+        staker: staker.clone(),
+        amount,
+        staked: position.staked,
+    }
+    .publish(env);
+    position.clone()
+}
+
+fn reward_token(env: &Env) -> token::Client<'_> {
+    let reward: Address = env.storage().instance().get(&AdminData::Reward).unwrap();
+    token::Client::new(env, &reward)
+}
+
+fn set(env: &Env, staker: &Address, position: &Staker) {
+    persist(env, &UserData::Staker(staker.clone()), position);
+}
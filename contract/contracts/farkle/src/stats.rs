@@ -0,0 +1,102 @@
+//! Lifetime statistics, kept per address across matches.
+
+use soroban_sdk::{contracttype, Address, Env};
+
+use crate::{persist, Outcome, UserData};
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct PlayerStats {
+    pub played: u32,
+    pub won: u32,
+    pub lost: u32,
+    pub forfeited: u32, // losses from stalling past the forfeit window
+    pub conceded: u32,  // losses from giving up
+    pub busts: u32,
+    pub hot_dice: u32,   // turns where all six dice scored and were re-rolled
+    pub best_turn: u32,  // most points banked in a single turn
+    pub best_score: u32, // highest total score ever banked
+    pub streak: u32,     // matches won in a row, up to the latest
+    pub wagered: i128,
+    pub winnings: i128,
+}
+
+/** Returns the stats on record for `player`, all zeroes for newcomers. */
+pub fn get(env: &Env, player: &Address) -> PlayerStats {
+    env.storage()
+        .persistent()
+        .get(&UserData::Stats(player.clone()))
+        .unwrap_or_default()
+}
+
+pub fn record_bust(env: &Env, player: &Address) {
+    let mut stats = get(env, player);
+    stats.busts += 1;
+    set(env, player, &stats);
+}
+
+pub fn record_hot_dice(env: &Env, player: &Address) {
+    let mut stats = get(env, player);
+    stats.hot_dice += 1;
+    set(env, player, &stats);
+}
+
+/** Records `turn` points being banked for a new total of `score`. */
+pub fn record_bank(env: &Env, player: &Address, turn: u32, score: u32) {
+    let mut stats = get(env, player);
+    stats.best_turn = stats.best_turn.max(turn);
+    stats.best_score = stats.best_score.max(score);
+    set(env, player, &stats);
+}
+
+/**
+ * Records a settled match where the winner and loser put up `stakes`,
+ * respectively, and the winner took home `payout`.
+ *
+ * Games that aren't wagered on their own (like those in a series) should
+ * pass zero for both, and record the wager with `record_wager` once the pot
+ * is actually paid out.
+ */
+pub fn record_result(
+    env: &Env,
+    winner: &Address,
+    loser: &Address,
+    outcome: &Outcome,
+    stakes: (i128, i128),
+    payout: i128,
+) {
+    let mut stats = get(env, winner);
+    stats.played += 1;
+    stats.won += 1;
+    stats.streak += 1;
+    set(env, winner, &stats);
+    record_wager(env, winner, stakes.0, payout);
+
+    stats = get(env, loser);
+    stats.played += 1;
+    stats.lost += 1;
+    stats.streak = 0;
+    match outcome {
+        Outcome::Forfeit => stats.forfeited += 1,
+        Outcome::Concede => stats.conceded += 1,
+        Outcome::Win | Outcome::Aborted | Outcome::Deadline => {}
+    }
+    set(env, loser, &stats);
+    record_wager(env, loser, stakes.1, 0);
+}
+
+/** Records `player` putting up `stake` and collecting `payout` for it. */
+pub fn record_wager(env: &Env, player: &Address, stake: i128, payout: i128) {
+    if stake == 0 && payout == 0 {
+        return;
+    }
+
+    let mut stats = get(env, player);
+    stats.wagered += stake;
+    stats.winnings += payout;
+    set(env, player, &stats);
+}
+
+fn set(env: &Env, player: &Address, stats: &PlayerStats) {
+    persist(env, &UserData::Stats(player.clone()), stats);
+}
@@ -2,37 +2,80 @@
 extern crate std;
 
 use super::*;
-use soroban_sdk::testutils::Address as _;
+use soroban_sdk::testutils::{Address as _, EnvTestConfig, Ledger as _};
 use soroban_sdk::xdr::{self, Limits, WriteXdr as _};
-use soroban_sdk::{Address, Bytes, Env};
+use soroban_sdk::{contract, contractimpl, contracttype, Address, Bytes, Env};
 
 const INIT: i128 = 2000 * ONE_XLM;
 
-#[test]
-fn test() {
-    let env = Env::default();
+// Mirrors the native asset contract's balance entry so tests can fund
+// accounts: XLM has no issuer, so there's no way to `mint` it.
+#[contracttype]
+enum NativeKey {
+    Balance(Address),
+}
+
+#[contracttype]
+struct NativeBalance {
+    amount: i128,
+    authorized: bool,
+    clawback: bool,
+}
+
+fn fund(env: &Env, xlm: &Address, who: &Address, amount: i128) {
+    env.as_contract(xlm, || {
+        env.storage().persistent().set(
+            &NativeKey::Balance(who.clone()),
+            &NativeBalance {
+                amount,
+                authorized: true,
+                clawback: false,
+            },
+        );
+    });
+}
+
+/** Deploys the game against a testnet-style XLM contract and a reward token. */
+fn setup(env: &Env) -> (FarkleClient<'_>, token::Client<'_>, Address, Address) {
     env.mock_all_auths();
 
-    let admin = Address::generate(&env);
-    let sac = env.register_stellar_asset_contract_v2(admin.clone());
+    // The contract hardcodes the testnet XLM contract, so match its network.
+    let passphrase = Bytes::from_slice(env, b"Test SDF Network ; September 2015");
+    env.ledger()
+        .set_network_id(env.crypto().sha256(&passphrase).to_array());
+
+    let admin = Address::generate(env);
+    let reward = env.register_stellar_asset_contract_v2(admin.clone());
     let xlm = env
         .deployer()
         .with_stellar_asset(Bytes::from_slice(
-            &env,
+            env,
             &xdr::Asset::Native.to_xdr(Limits::none()).unwrap(),
         ))
-        .deployed_address();
-    env.register(xlm.clone(), ());
+        .deploy();
 
-    let sac_admin = token::StellarAssetClient::new(&env, &sac.address());
-    let sac_client = token::Client::new(&env, &sac.address());
+    let client = FarkleClient::new(env, &env.register(Farkle, (&admin, reward.address())));
+    assert_eq!(client.token(), xlm);
+
+    (
+        client,
+        token::Client::new(env, &xlm),
+        admin,
+        reward.address(),
+    )
+}
+
+#[test]
+fn test() {
+    let env = Env::default();
+    let (client, sac_client, _, _) = setup(&env);
+    let xlm = sac_client.address.clone();
 
-    let client = FarkleClient::new(&env, &env.register(Farkle, (&admin,)));
     let alice = Address::generate(&env);
     let bob = Address::generate(&env);
 
-    sac_admin.mint(&alice, &INIT);
-    sac_admin.mint(&bob, &INIT);
+    fund(&env, &xlm, &alice, INIT);
+    fund(&env, &xlm, &bob, INIT);
 
     let balance: i128 = 1000 * ONE_XLM;
     assert_eq!(client.deposit(&alice, &balance), balance);
@@ -95,6 +138,20 @@ fn test() {
     assert_eq!(rv.len(), 6);
 
     rv = roll(&client, &alice, vec![&env, 0, 3, 4, 5], true);
+    assert_eq!(client.score(&alice), 2700);
+    assert_eq!(rv.len(), 0);
+
+    rv = roll(&client, &bob, vec![&env], false);
+    assert_eq!(rv.len(), 6); // 2 2 5 3 1 5
+
+    rv = roll(&client, &bob, vec![&env, 2, 4, 5], true);
+    assert_eq!(client.score(&bob), 1050 + 200);
+    assert_eq!(rv.len(), 0);
+
+    rv = roll(&client, &alice, vec![&env], false);
+    assert_eq!(rv.len(), 6); // 6 6 6 2 6 3
+
+    rv = roll(&client, &alice, vec![&env, 0, 1, 2, 4], true);
     assert_eq!(client.score(&alice), 0);
     assert_eq!(client.score(&bob), 0);
     assert_eq!(rv.len(), 0);
@@ -102,20 +159,1527 @@ fn test() {
     assert_eq!(
         sac_client.balance(&alice),
         // Alice has 0 in account, plus winnings
-        2 * COST_TO_PLAY - (ONE_XLM * 10)
+        Farkle::get_payout(COST_TO_PLAY, COST_TO_PLAY)
     );
 
-    assert_eq!(client.balance(&alice), 1000 * ONE_XLM);
-    assert_eq!(client.withdraw(&alice), 1000 * ONE_XLM); // alice still has $ for a game
-    assert_eq!(client.withdraw(&bob), 0); // bob lost so 0
+    assert_eq!(client.balance(&alice), balance * 2 - COST_TO_PLAY);
+    assert_eq!(client.withdraw(&alice), balance * 2 - COST_TO_PLAY); // alice still has $ for a game
+    assert_eq!(client.withdraw(&bob), balance - COST_TO_PLAY); // bob lost his stake
 
     assert_eq!(client.deposit(&alice, &INIT), INIT);
 
-    assert_eq!(sac_client.balance(&admin), INIT + (10 * ONE_XLM));
+    assert_eq!(client.fees_accrued(), 2 * Farkle::get_fee(COST_TO_PLAY));
+}
+
+#[test]
+fn test_held_twice() {
+    let env = seeded(0);
+    let (client, sac_client, _, _) = setup(&env);
+    let [alice, bob] = players(&env, &client, &sac_client);
+
+    let first = client.engage(&alice, &bob);
+    let dice = roll(&client, &first, Vec::new(&env), false);
+    assert_eq!(dice, vec![&env, 6, 1, 6, 2, 4, 5]);
+
+    // The one 1 can't be counted as three of a kind by naming it thrice.
+    assert_eq!(
+        client.try_roll(&first, &vec![&env, 1, 1, 1], &true),
+        Err(Ok(Error::BadDieHold.into()))
+    );
+    assert_eq!(
+        client.try_roll(&first, &vec![&env, 1, 1], &true),
+        Err(Ok(Error::BadDieHold.into()))
+    );
+    roll(&client, &first, vec![&env, 1], true);
+    assert_eq!(client.score(&first), 100);
+}
+
+#[test]
+fn test_records() {
+    let env = seeded(1);
+    let (client, sac_client, _, reward) = setup(&env);
+    let [alice, bob] = players(&env, &client, &sac_client);
+
+    assert_eq!(play_out(&env, &client, &alice, &bob), alice);
+
+    // Rewards wait in the contract until they're claimed.
+    let reward = token::Client::new(&env, &reward);
+    assert_eq!(client.pending_rewards(&alice), REWARD * 2);
+    assert_eq!(client.pending_rewards(&bob), REWARD);
+    assert_eq!(client.claim_rewards(&alice), REWARD * 2);
+    assert_eq!(client.claim_rewards(&alice), 0);
+    assert_eq!(reward.balance(&alice), REWARD * 2);
+    assert_eq!(client.pending_rewards(&alice), 0);
+
+    // Evenly-matched newcomers trade half of the K-factor.
+    assert_eq!(client.rating(&alice), rating::DEFAULT_RATING + 16);
+    assert_eq!(client.rating(&bob), rating::DEFAULT_RATING - 16);
+
+    assert_eq!(
+        client.stats(&alice),
+        PlayerStats {
+            played: 1,
+            won: 1,
+            best_turn: 900,
+            best_score: 3150,
+            streak: 1,
+            wagered: COST_TO_PLAY,
+            winnings: Farkle::get_payout(COST_TO_PLAY, COST_TO_PLAY),
+            ..Default::default()
+        }
+    );
+    assert_eq!(client.stats(&bob).lost, 1);
+    assert_eq!(client.stats(&bob).best_score, 2900);
+
+    let history = client.match_history(&bob, &0, &10);
+    assert_eq!(history.len(), 1);
+    let result = history.get(0).unwrap();
+    assert_eq!((result.a, result.b), (alice.clone(), bob.clone()));
+    assert_eq!((result.score_a, result.score_b), (3150, 2900));
+    assert_eq!(result.winner, Some(alice.clone()));
+    assert_eq!(result.outcome, Outcome::Win);
+    assert_eq!(client.recent_matches(&0, &10), history);
+}
+
+#[test]
+fn test_concede() {
+    let env = seeded(0);
+    let (client, sac_client, _, _) = setup(&env);
+    let [alice, bob] = players(&env, &client, &sac_client);
+
+    client.engage(&alice, &bob);
+    client.concede(&bob);
+
+    assert_eq!(
+        sac_client.balance(&alice),
+        INIT / 2 + Farkle::get_payout(COST_TO_PLAY, COST_TO_PLAY)
+    );
+    assert_eq!(client.score(&alice), 0);
+    assert_eq!(client.rating(&alice), rating::DEFAULT_RATING + 16);
+    assert_eq!(client.rating(&bob), rating::DEFAULT_RATING - 16);
+    assert_eq!(client.stats(&bob).conceded, 1);
+    assert_eq!(client.stats(&bob).forfeited, 0);
+
+    // The match is over, so there's nothing left to concede.
+    assert_eq!(
+        client.try_concede(&alice),
+        Err(Ok(Error::NotYourTurn.into()))
+    );
+
+    // An upset is worth more than beating someone you should beat.
+    client.engage(&alice, &bob);
+    client.concede(&alice);
+    assert_eq!(client.rating(&bob), rating::DEFAULT_RATING - 16 + 17);
+    assert_eq!(client.rating(&alice), rating::DEFAULT_RATING + 16 - 17);
+
+    let record = client.head_to_head(&bob, &alice);
+    assert_eq!((record.a, record.b), (bob.clone(), alice.clone()));
+    assert_eq!((record.a_wins, record.b_wins), (1, 1));
+    assert_eq!(record.last_winner, Some(bob.clone()));
+    assert_eq!(record.played, 2);
+    assert_eq!(record.last_outcome, Outcome::Concede);
+
+    // Matches the admin ends have no winner, but still count as played.
+    env.ledger().set_sequence_number(100);
+    client.engage(&alice, &bob);
+    client.end_match(&alice, &bob);
+
+    let record = client.head_to_head(&alice, &bob);
+    assert_eq!((record.a_wins, record.b_wins), (1, 1));
+    assert_eq!(record.last_winner, None);
+    assert_eq!(record.played, 3);
+    assert_eq!(record.last_outcome, Outcome::Aborted);
+    assert_eq!(record.last_ledger, 100);
+
+    // History pages run from the newest match backwards.
+    let history = client.match_history(&alice, &0, &10);
+    assert_eq!(history.len(), 3);
+    assert_eq!(history.get(0).unwrap().outcome, Outcome::Aborted);
+    assert_eq!(history.get(0).unwrap().start_ledger, 100);
+    assert_eq!(history.get(1).unwrap().winner, Some(bob.clone()));
+    assert_eq!(history.get(2).unwrap().winner, Some(alice.clone()));
+
+    let page = client.recent_matches(&1, &1);
+    assert_eq!(page.len(), 1);
+    assert_eq!(page.get(0).unwrap().id, 1);
+    assert_eq!(client.match_history(&bob, &3, &10).len(), 0);
+}
+
+#[test]
+fn test_rematch() {
+    let env = seeded(0);
+    let (client, sac_client, _, _) = setup(&env);
+    let [alice, bob, carol] = players(&env, &client, &sac_client);
+
+    // Nobody has played yet, so there's nothing to rematch.
+    assert_eq!(
+        client.try_offer_rematch(&alice),
+        Err(Ok(Error::NoRematch.into()))
+    );
+
+    client.engage(&alice, &bob);
+    client.concede(&bob);
+
+    // Bob can't accept an offer that hasn't been made.
+    assert_eq!(
+        client.try_accept_rematch(&bob),
+        Err(Ok(Error::NoRematch.into()))
+    );
+
+    client.offer_rematch(&alice);
+    assert_eq!(client.accept_rematch(&bob), bob); // the loser goes first
+    assert_eq!(client.balance(&bob), INIT / 2 - 2 * COST_TO_PLAY);
+
+    // The offer was used up by starting the rematch.
+    client.concede(&alice);
+    client.offer_rematch(&bob);
+    // ...and starting another match moves on from the last one.
+    client.engage(&alice, &carol);
+    assert_eq!(
+        client.try_accept_rematch(&alice),
+        Err(Ok(Error::NoRematch.into()))
+    );
+}
+
+#[test]
+fn test_series() {
+    let env = seeded(0);
+    let (client, sac_client, _, _) = setup(&env);
+    let [alice, bob] = players(&env, &client, &sac_client);
+
+    assert_eq!(
+        client.try_engage_series(&alice, &bob, &4),
+        Err(Ok(Error::InvalidAmount.into()))
+    );
+
+    let id = client.engage_series(&alice, &bob, &3);
+    assert_eq!(client.balance(&alice), INIT / 2 - COST_TO_PLAY);
+
+    // Games are ordinary matches, so players can't wander off mid-series.
+    assert_eq!(
+        client.try_engage(&alice, &bob),
+        Err(Ok(Error::AlreadyPlaying.into()))
+    );
+
+    client.concede(&alice);
+    let series = client.series_state(&id);
+    assert_eq!((series.a_wins, series.b_wins, series.over), (0, 1, false));
+
+    // Nothing is paid out per game, and the next one is already underway.
+    assert_eq!(sac_client.balance(&bob), INIT / 2);
+    assert_eq!(client.pending_rewards(&bob), 0);
+    client.concede(&bob);
+    client.concede(&alice);
+
+    let series = client.series_state(&id);
+    assert_eq!((series.a_wins, series.b_wins, series.over), (1, 2, true));
+    assert_eq!(series.winner, Some(bob.clone()));
+    assert_eq!(
+        sac_client.balance(&bob),
+        INIT / 2 + Farkle::get_payout(COST_TO_PLAY, COST_TO_PLAY)
+    );
+    assert_eq!(client.balance(&bob), INIT / 2 - COST_TO_PLAY);
+
+    // Rewards are earned once for the series, not for each game.
+    assert_eq!(client.pending_rewards(&bob), REWARD * 2);
+    assert_eq!(client.pending_rewards(&alice), REWARD);
+
+    let stats = client.stats(&bob);
+    assert_eq!((stats.played, stats.won), (3, 2));
+    assert_eq!(
+        (stats.wagered, stats.winnings),
+        (COST_TO_PLAY, Farkle::get_payout(COST_TO_PLAY, COST_TO_PLAY))
+    );
+
+    // Once it's over, the players are free again.
+    client.engage(&alice, &bob);
+}
+
+#[test]
+fn test_abort_series() {
+    let env = seeded(0);
+    let (client, sac_client, _, _) = setup(&env);
+    let [alice, bob] = players(&env, &client, &sac_client);
+    let fee = Farkle::get_fee(COST_TO_PLAY);
+
+    let id = client.engage_series(&alice, &bob, &3);
+    client.concede(&alice);
+    assert_eq!(
+        client.try_abort_series(&id),
+        Err(Ok(Error::AlreadyPlaying.into()))
+    );
+
+    // Nobody plays on, and the game in progress lapses.
+    env.ledger().with_mut(|l| l.sequence_number += 1000);
+    client.abort_series(&id);
+
+    let series = client.series_state(&id);
+    assert_eq!((series.over, series.winner), (true, None));
+    assert_eq!(client.balance(&alice), INIT / 2 - fee);
+    assert_eq!(client.balance(&bob), INIT / 2 - fee);
+    assert_eq!(
+        client.try_abort_series(&id),
+        Err(Ok(Error::WrongMatch.into()))
+    );
+}
+
+#[test]
+fn test_doubling_cube() {
+    let env = seeded(0);
+    let (client, sac_client, _, _) = setup(&env);
+    let [alice, bob] = players(&env, &client, &sac_client);
+
+    assert_eq!(client.engage(&alice, &bob), alice);
+    assert_eq!(
+        client.try_offer_double(&bob),
+        Err(Ok(Error::NotYourTurn.into()))
+    );
+
+    client.offer_double(&alice);
+    assert_eq!(client.cube(&bob).offered, Some(alice.clone()));
+    assert_eq!(
+        client.try_roll(&alice, &vec![&env], &false),
+        Err(Ok(Error::DoublePending.into()))
+    );
+
+    client.accept_double(&bob);
+    assert_eq!(client.balance(&bob), INIT / 2 - 2 * COST_TO_PLAY);
+    assert_eq!(client.balance(&alice), INIT / 2 - 2 * COST_TO_PLAY);
+
+    let cube = client.cube(&alice);
+    assert_eq!(
+        (cube.value, cube.owner, cube.offered),
+        (2, Some(bob.clone()), None)
+    );
+
+    // Only Bob may double from here on, and only once he's on turn.
+    assert_eq!(
+        client.try_offer_double(&alice),
+        Err(Ok(Error::CantDouble.into()))
+    );
+
+    client.concede(&alice);
+    assert_eq!(
+        sac_client.balance(&bob),
+        INIT / 2 + Farkle::get_payout(2 * COST_TO_PLAY, 2 * COST_TO_PLAY)
+    );
+
+    // Turning a double down loses at the stakes from before it.
+    assert_eq!(client.engage(&alice, &bob), bob);
+    let before = sac_client.balance(&bob);
+    client.offer_double(&bob);
+    client.decline_double(&alice);
+    assert_eq!(
+        sac_client.balance(&bob),
+        before + Farkle::get_payout(COST_TO_PLAY, COST_TO_PLAY)
+    );
+    assert_eq!(client.stats(&alice).conceded, 2);
+}
+
+#[test]
+fn test_custom_terms() {
+    let env = seeded(0);
+    let (client, sac_client, _, _) = setup(&env);
+    let [alice, bob] = players(&env, &client, &sac_client);
+
+    // Alice is the stronger player, so she puts up more and gives Bob a head
+    // start and a lower target.
+    let terms = MatchTerms {
+        stake_a: 2 * COST_TO_PLAY,
+        stake_b: COST_TO_PLAY,
+        head_start_a: 0,
+        head_start_b: 500,
+        target_a: 0,
+        target_b: 2000,
+    };
+
+    let bad = MatchTerms {
+        head_start_b: 2000,
+        ..terms.clone()
+    };
+    assert_eq!(
+        client.try_engage_with(&alice, &bob, &bad),
+        Err(Ok(Error::InvalidAmount.into()))
+    );
+
+    // Token stakes would farm rewards for next to no fee.
+    let bad = MatchTerms {
+        stake_b: 1,
+        ..terms.clone()
+    };
+    assert_eq!(
+        client.try_engage_with(&alice, &bob, &bad),
+        Err(Ok(Error::InvalidAmount.into()))
+    );
+    let bad = MatchTerms {
+        stake_b: COST_TO_PLAY - 1,
+        ..terms.clone()
+    };
+    assert_eq!(
+        client.try_engage_with(&alice, &bob, &bad),
+        Err(Ok(Error::InvalidAmount.into()))
+    );
+
+    client.engage_with(&alice, &bob, &terms);
+    assert_eq!(client.balance(&alice), INIT / 2 - 2 * COST_TO_PLAY);
+    assert_eq!(client.balance(&bob), INIT / 2 - COST_TO_PLAY);
+    assert_eq!((client.score(&alice), client.score(&bob)), (0, 500));
+
+    // Bob takes both stakes if he wins.
+    client.concede(&alice);
+    assert_eq!(
+        sac_client.balance(&bob),
+        INIT / 2 + Farkle::get_payout(COST_TO_PLAY, 2 * COST_TO_PLAY)
+    );
+
+    let result = client.match_history(&alice, &0, &1).get(0).unwrap();
+    assert_eq!(result.a, bob); // the winner's side comes first
+    assert_eq!(
+        (result.stake_a, result.stake_b),
+        (COST_TO_PLAY, 2 * COST_TO_PLAY)
+    );
+    assert_eq!(result.score_a, 500);
+
+    // A rematch is played on the same terms.
+    client.offer_rematch(&bob);
+    client.accept_rematch(&alice);
+    assert_eq!(client.balance(&alice), INIT / 2 - 4 * COST_TO_PLAY);
+    assert_eq!((client.score(&alice), client.score(&bob)), (0, 500));
+}
+
+#[test]
+fn test_house() {
+    let env = seeded(0);
+    let (client, sac_client, admin, _) = setup(&env);
+    let alice = player(&env, &client, &sac_client);
+    let stake = 5 * COST_TO_PLAY;
+
+    // Nothing can be wagered until the admin funds the bankroll.
+    assert_eq!(
+        client.try_engage_house(&alice, &stake),
+        Err(Ok(Error::HouseLimit.into()))
+    );
+
+    fund(&env, &sac_client.address, &admin, INIT);
+    client.fund_house(&(20 * COST_TO_PLAY));
+    client.set_house_limit(&(4 * COST_TO_PLAY));
+    assert_eq!(
+        client.try_engage_house(&alice, &stake),
+        Err(Ok(Error::HouseLimit.into()))
+    );
+
+    client.set_house_limit(&(10 * COST_TO_PLAY));
+    assert_eq!(
+        client.try_engage_house(&alice, &(COST_TO_PLAY - 1)),
+        Err(Ok(Error::InvalidAmount.into()))
+    );
+    client.engage_house(&alice, &stake);
+    assert_eq!(client.bankroll().exposure, stake);
+    assert_eq!(client.balance(&alice), INIT / 2 - stake);
+
+    // Only what isn't at stake can be taken back out.
+    assert_eq!(
+        client.try_drain_house(&(16 * COST_TO_PLAY)),
+        Err(Ok(Error::InvalidAmount.into()))
+    );
+
+    // Alice banks whatever she's first dealt, and the house answers each
+    // turn on its own, until someone wins.
+    let play = || {
+        let played = client.match_history(&alice, &0, &10).len();
+        let mut turns = 0;
+        while client.match_history(&alice, &0, &10).len() == played {
+            let dice = roll(&client, &alice, Vec::new(&env), false);
+            let save = scoring(&env, &dice);
+            if !save.is_empty() {
+                roll(&client, &alice, save, true);
+            }
+            turns += 1;
+        }
+        turns
+    };
+
+    // The house wins the first match...
+    assert_eq!(play(), 4);
+    let result = client.match_history(&alice, &0, &1).get(0).unwrap();
+    assert_eq!(result.winner, Some(client.address.clone()));
+    assert_eq!((result.score_a, result.score_b), (3050, 1350));
+    let bank = client.bankroll();
+    assert_eq!(bank.exposure, 0);
+    assert_eq!(
+        bank.balance,
+        20 * COST_TO_PLAY + stake - Farkle::get_fee(stake)
+    );
+    assert_eq!(sac_client.balance(&alice), INIT / 2);
+
+    // ...and Alice the second, where only her side of the pot carries a fee.
+    client.engage_house(&alice, &stake);
+    assert_eq!(play(), 6);
+    let result = client.match_history(&alice, &0, &1).get(0).unwrap();
+    assert_eq!(result.winner, Some(alice.clone()));
+    assert_eq!((result.score_a, result.score_b), (3200, 1150));
+    let bank = client.bankroll();
+    assert_eq!(bank.exposure, 0);
+    assert_eq!(bank.balance, 20 * COST_TO_PLAY - Farkle::get_fee(stake));
+    assert_eq!(
+        sac_client.balance(&alice),
+        INIT / 2 + 2 * stake - Farkle::get_fee(stake)
+    );
+    assert_eq!(client.house_score(&alice), 0);
+}
+
+#[test]
+fn test_fee_policy() {
+    let env = seeded(0);
+    let (client, sac_client, _, _) = setup(&env);
+    let [alice, bob] = players(&env, &client, &sac_client);
+    let fee = Farkle::get_fee(COST_TO_PLAY);
+
+    // Whatever's in the contract is owed to a player or to the vault.
+    let solvent = || {
+        let owed = client.balance(&alice) + client.balance(&bob) + client.fees_accrued();
+        assert_eq!(sac_client.balance(&client.address), owed);
+    };
+
+    // By default, an aborted match still pays the fee.
+    assert_eq!(client.fee_policy(), FeePolicy::OnEngage);
+    client.engage(&alice, &bob);
+    assert_eq!(client.fees_accrued(), 2 * fee);
+    client.end_match(&alice, &bob);
+    assert_eq!(client.balance(&alice), INIT / 2 - fee);
+    solvent();
+
+    // Once fees are only charged on settlement, it doesn't.
+    client.set_fee_policy(&FeePolicy::OnSettle);
+    client.engage(&alice, &bob);
+    assert_eq!(client.fees_accrued(), 2 * fee);
+    client.end_match(&alice, &bob);
+    assert_eq!(client.balance(&alice), INIT / 2 - fee);
+    assert_eq!(client.balance(&bob), INIT / 2 - fee);
+    solvent();
+
+    // A match that's won pays the same fee either way.
+    client.engage(&alice, &bob);
+    client.concede(&alice);
+    assert_eq!(client.fees_accrued(), 4 * fee);
+    assert_eq!(
+        sac_client.balance(&bob),
+        INIT / 2 + Farkle::get_payout(COST_TO_PLAY, COST_TO_PLAY)
+    );
+    solvent();
+
+    // The admin sends what's in the vault wherever they like.
+    assert_eq!(
+        client.try_collect_fees(&alice, &(4 * fee + 1)),
+        Err(Ok(Error::InvalidAmount.into()))
+    );
+    let treasury = Address::generate(&env);
+    assert_eq!(client.collect_fees(&treasury, &fee), 3 * fee);
+    assert_eq!(sac_client.balance(&treasury), fee);
+    solvent();
+
+    // Or splits it between recipients with set shares.
+    let (dev, ops) = (Address::generate(&env), Address::generate(&env));
+    assert_eq!(
+        client.try_split_fees(&fee),
+        Err(Ok(Error::InvalidAmount.into()))
+    );
+    let share = |to: &Address, share| FeeShare {
+        to: to.clone(),
+        share,
+    };
+    for bad in [
+        vec![&env],
+        vec![&env, share(&treasury, 600), share(&dev, 300)],
+        vec![&env, share(&treasury, 1000), share(&dev, 0)],
+    ] {
+        assert_eq!(
+            client.try_set_fee_split(&bad),
+            Err(Ok(Error::InvalidAmount.into()))
+        );
+    }
+    let split = vec![
+        &env,
+        share(&treasury, 500),
+        share(&dev, 300),
+        share(&ops, 200),
+    ];
+    client.set_fee_split(&split);
+    assert_eq!(client.fee_split(), split);
+
+    let amount = 2 * fee + 1;
+    assert_eq!(client.split_fees(&amount), 3 * fee - amount);
+    assert_eq!(sac_client.balance(&dev), amount * 3 / 10);
+    assert_eq!(sac_client.balance(&ops), amount / 5);
+    assert_eq!(
+        sac_client.balance(&treasury),
+        fee + amount - amount * 3 / 10 - amount / 5
+    );
+    solvent();
+}
+
+#[test]
+fn test_jackpot() {
+    let env = seeded(0);
+    let (client, sac_client, _, _) = setup(&env);
+    let [alice, bob] = players(&env, &client, &sac_client);
+    let fee = Farkle::get_fee(COST_TO_PLAY);
+
+    let triggers = vec![
+        &env,
+        JackpotTrigger {
+            pattern: JackpotPattern::OfAKind(4),
+            opening: true,
+        },
+    ];
+    client.set_jackpot(&JackpotConfig {
+        share: 500,
+        payout: 1000,
+        triggers,
+    });
+
+    // Practice rolls never win it.
+    client.practice_engage(&alice, &bob);
+    client.concede(&alice);
+
+    // Each match pays half its fees into the pool. Four go by without an
+    // opening roll of four of a kind...
+    for n in 1..=4 {
+        let first = client.engage(&alice, &bob);
+        assert_eq!(client.jackpot(), n * fee);
+        roll(&client, &first, vec![&env], false);
+        assert_eq!(client.jackpot(), n * fee);
+        client.concede(&first);
+    }
+
+    // ...until Alice opens the fifth with one, and takes the lot.
+    assert_eq!(client.engage(&alice, &bob), alice);
+    let before = client.balance(&alice);
+    let dice = roll(&client, &alice, vec![&env], false);
+    assert_eq!(dice, vec![&env, 5, 4, 4, 5, 5, 5]);
+    assert_eq!(client.jackpot(), 0);
+    assert_eq!(client.balance(&alice), before + 5 * fee);
+    assert_eq!(client.fees_accrued(), 5 * fee);
+    client.concede(&alice);
+
+    let owed = client.balance(&alice) + client.balance(&bob) + client.fees_accrued();
+    assert_eq!(sac_client.balance(&client.address), owed);
+
+    assert_eq!(
+        client.try_set_jackpot(&JackpotConfig {
+            share: 1001,
+            payout: 1000,
+            triggers: Vec::new(&env),
+        }),
+        Err(Ok(Error::InvalidAmount.into()))
+    );
+}
+
+#[test]
+fn test_referrals() {
+    let env = seeded(0);
+    let (client, sac_client, _, _) = setup(&env);
+    let [alice, bob] = players(&env, &client, &sac_client);
+    let streamer = Address::generate(&env);
+    let fee = Farkle::get_fee(COST_TO_PLAY);
+
+    client.set_referrer(&alice, &streamer);
+    assert_eq!(client.referrer(&alice), Some(streamer.clone()));
+    assert_eq!(
+        client.try_set_referrer(&alice, &bob),
+        Err(Ok(Error::AlreadyReferred.into()))
+    );
+    assert_eq!(
+        client.try_set_referrer(&bob, &bob),
+        Err(Ok(Error::InvalidAmount.into()))
+    );
+
+    // A fifth of Alice's fee goes to the streamer, and none of Bob's.
+    client.set_referral_share(&200);
+    client.engage(&alice, &bob);
+    client.concede(&bob);
+
+    let cut = fee / 5;
+    assert_eq!(client.balance(&streamer), cut);
+    assert_eq!(client.fees_accrued(), 2 * fee - cut);
+    assert_eq!(
+        client.referrals(&streamer),
+        Referrals {
+            referred: 1,
+            earned: cut
+        }
+    );
+
+    // What's shared out can't also go to the jackpot.
+    assert_eq!(
+        client.try_set_jackpot(&JackpotConfig {
+            share: 900,
+            payout: 1000,
+            triggers: Vec::new(&env),
+        }),
+        Err(Ok(Error::InvalidAmount.into()))
+    );
+
+    assert_eq!(client.withdraw(&streamer), cut);
+    assert_eq!(sac_client.balance(&streamer), cut);
+    let owed = client.balance(&alice) + client.balance(&bob) + client.fees_accrued();
+    assert_eq!(sac_client.balance(&client.address), owed);
+
+    // Entry fees pay the streamer their share too.
+    let closes = env.ledger().sequence() + 100;
+    let id = client.open_contest(&COST_TO_PLAY, &3, &closes, &vec![&env, 1000]);
+    client.enter_contest(&alice, &id);
+    client.enter_contest(&bob, &id);
+    assert_eq!(client.balance(&streamer), cut);
+    assert_eq!(client.fees_accrued(), 4 * fee - 2 * cut);
+    assert_eq!(client.referrals(&streamer).earned, 2 * cut);
+}
+
+#[test]
+fn test_reward_schedule() {
+    let env = seeded(0);
+    let (client, sac_client, _, _) = setup(&env);
+    let [alice, bob] = players(&env, &client, &sac_client);
+
+    let schedule = RewardSchedule {
+        base: 1000,
+        winner: 2000,
+        loser: 1000,
+        streak_bonus: 500,
+        max_streak: 2,
+        epoch_length: 100,
+        epoch_cap: 6000,
+        decay: 500,
+        daily_cap: 4000,
+    };
+
+    // Multipliers and amounts are bounded, so neither a long streak nor a
+    // huge base or cap can overflow the sums made with them.
+    for bad in [
+        RewardSchedule {
+            winner: 10_001,
+            ..schedule.clone()
+        },
+        RewardSchedule {
+            max_streak: u32::MAX,
+            ..schedule.clone()
+        },
+        RewardSchedule {
+            base: i128::MAX,
+            ..schedule.clone()
+        },
+        RewardSchedule {
+            epoch_cap: i128::MAX,
+            ..schedule.clone()
+        },
+    ] {
+        assert_eq!(
+            client.try_set_reward_schedule(&bad),
+            Err(Ok(Error::InvalidAmount.into()))
+        );
+    }
+    client.set_reward_schedule(&schedule);
+
+    assert_eq!(play_out(&env, &client, &alice, &bob), bob);
+    assert_eq!(client.pending_rewards(&bob), 2000);
+    assert_eq!(client.pending_rewards(&alice), 1000);
+    assert_eq!(client.reward_epoch().emitted, 3000);
+
+    // Winning again earns a streak bonus, but only up to the daily cap...
+    assert_eq!(play_out(&env, &client, &alice, &bob), bob);
+    assert_eq!(client.pending_rewards(&bob), 4000); // rather than 2000 + 2500
+    assert_eq!(client.pending_rewards(&alice), 2000);
+    assert_eq!(client.reward_epoch().emitted, 6000);
+
+    // ...and once the epoch's cap is reached, nobody earns any more.
+    assert_eq!(play_out(&env, &client, &alice, &bob), alice);
+    assert_eq!(client.pending_rewards(&alice), 2000);
+    assert_eq!(client.reward_epoch().emitted, 6000);
+
+    // Each epoch's cap is half the last one's.
+    env.ledger().set_sequence_number(100);
+    assert_eq!(client.reward_epoch().cap, 3000);
+    env.ledger().set_sequence_number(1000);
+    assert_eq!(client.reward_epoch().epoch, 10);
+    assert_eq!(client.reward_epoch().cap, 6000 / 1024);
+}
+
+#[test]
+fn test_reward_guard() {
+    let env = seeded(0);
+    let (client, sac_client, _, _) = setup(&env);
+    let [alice, bob] = players(&env, &client, &sac_client);
+    let pending = || client.pending_rewards(&alice) + client.pending_rewards(&bob);
+
+    client.set_reward_guard(&RewardGuard {
+        pair_cooldown: 1000,
+        ..Default::default()
+    });
+    play_out(&env, &client, &alice, &bob);
+    assert_eq!(pending(), REWARD * 3);
+    assert_eq!(client.reward_cooldown(&bob, &alice), 1000);
+
+    // Playing each other again so soon earns nothing.
+    play_out(&env, &client, &alice, &bob);
+    assert_eq!(pending(), REWARD * 3);
+
+    // Neither does a match won within a few ledgers of starting.
+    env.ledger().set_sequence_number(1000);
+    client.set_reward_guard(&RewardGuard {
+        min_ledgers: 10,
+        ..Default::default()
+    });
+    play_out(&env, &client, &alice, &bob);
+    assert_eq!(pending(), REWARD * 3);
+
+    // Nor does a series, timed from its opening game to the one deciding it.
+    client.engage_series(&alice, &bob, &3);
+    client.concede(&alice);
+    client.concede(&alice);
+    assert_eq!(pending(), REWARD * 3);
+
+    client.engage_series(&alice, &bob, &3);
+    client.concede(&alice);
+    env.ledger().set_sequence_number(1010);
+    client.concede(&alice);
+    assert_eq!(pending(), REWARD * 6);
+
+    // Once the guard is lifted, the pair earns again.
+    client.set_reward_guard(&RewardGuard::default());
+    play_out(&env, &client, &alice, &bob);
+    assert_eq!(pending(), REWARD * 9);
+
+    // A loser who gives up most of their matches is a likely second account.
+    client.set_reward_guard(&RewardGuard {
+        max_give_ups: 500,
+        ..Default::default()
+    });
+    for _ in 0..5 {
+        client.engage(&alice, &bob);
+        client.concede(&alice);
+    }
+    assert_eq!(play_out(&env, &client, &alice, &bob), bob);
+    assert_eq!(pending(), REWARD * 9);
+}
+
+#[test]
+fn test_staking() {
+    let env = seeded(0);
+    let (client, sac_client, _, reward) = setup(&env);
+    let [alice, bob] = players(&env, &client, &sac_client);
+    let fee = Farkle::get_fee(COST_TO_PLAY);
+
+    let minter = StellarAssetClient::new(&env, &reward);
+    let (big, small) = (Address::generate(&env), Address::generate(&env));
+    minter.mint(&big, &2000);
+    minter.mint(&small, &1000);
+
+    client.set_staking_share(&500);
+    client.stake(&big, &2000);
+    client.stake(&small, &1000);
+    assert_eq!(client.staking_pool().staked, 3000);
+
+    // Half of each fee goes to stakers, split two to one.
+    client.engage(&alice, &bob);
+    client.concede(&bob);
+    assert_eq!(client.fees_accrued(), fee);
+    assert_eq!(client.staker(&big).owed, fee * 2 / 3);
+    assert_eq!(client.staker(&small).owed, fee / 3);
+
+    // Unstaking keeps what was earned, but earns nothing more.
+    client.unstake(&small, &1000);
+    assert_eq!(token::Client::new(&env, &reward).balance(&small), 1000);
+    client.engage(&alice, &bob);
+    client.concede(&bob);
+    assert_eq!(client.staker(&big).owed, fee * 5 / 3);
+    assert_eq!(client.staker(&small).owed, fee / 3);
+
+    assert_eq!(client.claim(&big), fee * 5 / 3);
+    assert_eq!(client.claim(&big), 0);
+    assert_eq!(sac_client.balance(&big), fee * 5 / 3);
+    assert_eq!(
+        client.try_unstake(&small, &1),
+        Err(Ok(Error::InvalidAmount.into()))
+    );
+
+    // Nothing is lost to rounding: what's left over is carried until it adds
+    // up to whole stroops.
+    let owed = client.balance(&alice)
+        + client.balance(&bob)
+        + client.fees_accrued()
+        + client.staker(&small).owed;
+    let carried =
+        client.staking_pool().carry + client.staker(&big).carry + client.staker(&small).carry;
+    assert_eq!(
+        (sac_client.balance(&client.address) - owed) * staking::SCALE,
+        carried
+    );
+    assert_eq!(carried, staking::SCALE); // a single stroop
+
+    // Together, the shares paid out can't take more than the whole fee.
+    client.set_referral_share(&300);
+    assert_eq!(
+        client.try_set_staking_share(&800),
+        Err(Ok(Error::InvalidAmount.into()))
+    );
+}
+
+#[test]
+fn test_engage_from() {
+    let env = seeded(0);
+    let (client, sac_client, _, _) = setup(&env);
+    let (alice, bob) = (Address::generate(&env), Address::generate(&env));
+    fund(&env, &sac_client.address, &alice, INIT);
+    fund(&env, &sac_client.address, &bob, INIT);
+    let fee = Farkle::get_fee(COST_TO_PLAY);
+
+    // Without an allowance, there's nothing to pull the stakes from.
+    assert!(client.try_engage_from(&alice, &bob).is_err());
+
+    for p in [&alice, &bob] {
+        sac_client.approve(p, &client.address, &(3 * COST_TO_PLAY), &1000);
+    }
+    client.engage_from(&alice, &bob);
+    assert_eq!(sac_client.balance(&alice), INIT - COST_TO_PLAY);
+    assert_eq!(client.balance(&alice), -1); // never deposited
+    client.concede(&alice);
+    assert_eq!(
+        sac_client.balance(&bob),
+        INIT - COST_TO_PLAY + Farkle::get_payout(COST_TO_PLAY, COST_TO_PLAY)
+    );
+
+    // An aborted match gives the stakes back to the wallets, less the fee.
+    client.engage_from(&alice, &bob);
+    client.end_match(&alice, &bob);
+    assert_eq!(sac_client.balance(&alice), INIT - COST_TO_PLAY - fee);
+    assert_eq!(client.balance(&alice), -1);
+    assert_eq!(sac_client.balance(&client.address), client.fees_accrued());
+
+    // Doubling pulls the extra stakes from the wallets too, so long as the
+    // allowances cover them.
+    for p in [&alice, &bob] {
+        sac_client.approve(p, &client.address, &(2 * COST_TO_PLAY), &1000);
+    }
+    let first = client.engage_from(&alice, &bob);
+    let second = if first == alice {
+        bob.clone()
+    } else {
+        alice.clone()
+    };
+    sac_client.approve(&first, &client.address, &(COST_TO_PLAY - 1), &1000);
+    assert_eq!(
+        client.try_offer_double(&first),
+        Err(Ok(Error::TooPoor.into()))
+    );
+    sac_client.approve(&first, &client.address, &COST_TO_PLAY, &1000);
+    client.offer_double(&first);
+
+    let wallet = sac_client.balance(&second);
+    client.accept_double(&second);
+    assert_eq!(sac_client.balance(&second), wallet - COST_TO_PLAY);
+    client.concede(&first);
+    assert_eq!(
+        sac_client.balance(&second),
+        wallet - COST_TO_PLAY + Farkle::get_payout(2 * COST_TO_PLAY, 2 * COST_TO_PLAY)
+    );
+
+    // A rematch pulls its stakes from the wallets as well.
+    for p in [&alice, &bob] {
+        sac_client.approve(p, &client.address, &COST_TO_PLAY, &1000);
+    }
+    let wallet = sac_client.balance(&first);
+    client.offer_rematch(&second);
+    client.accept_rematch(&first);
+    assert_eq!(sac_client.balance(&first), wallet - COST_TO_PLAY);
+    assert_eq!(client.balance(&first), -1);
+    client.concede(&first);
+    assert_eq!(sac_client.balance(&first), wallet - COST_TO_PLAY);
+}
+
+#[test]
+fn test_withdraw_amount() {
+    let env = seeded(0);
+    let (client, sac_client, _, _) = setup(&env);
+    let [alice, bob] = players(&env, &client, &sac_client);
+    let carol = Address::generate(&env);
+
+    // Part of the balance can go to another address.
+    let left = client.withdraw_amount(&alice, &(100 * ONE_XLM), &carol);
+    assert_eq!(left, INIT / 2 - 100 * ONE_XLM);
+    assert_eq!(client.balance(&alice), left);
+    assert_eq!(sac_client.balance(&carol), 100 * ONE_XLM);
+    assert_eq!(sac_client.balance(&alice), INIT / 2);
+
+    assert_eq!(
+        client.try_withdraw_amount(&alice, &0, &carol),
+        Err(Ok(Error::InvalidAmount.into()))
+    );
+    assert_eq!(
+        client.try_withdraw_amount(&alice, &(left + 1), &alice),
+        Err(Ok(Error::TooPoor.into()))
+    );
+
+    // A stake held for a match can't be withdrawn.
+    client.engage(&bob, &alice);
+    let left = client.balance(&alice);
+    assert_eq!(
+        client.try_withdraw_amount(&alice, &(left + 1), &alice),
+        Err(Ok(Error::TooPoor.into()))
+    );
+    assert_eq!(client.withdraw_amount(&alice, &left, &alice), 0);
+    assert_eq!(sac_client.balance(&alice), INIT / 2 + left);
+}
+
+#[test]
+fn test_practice() {
+    let env = seeded(0);
+    let (client, _, _, _) = setup(&env);
+
+    // Neither player has deposited anything.
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    client.practice_engage(&alice, &bob);
+
+    let mut turns = 0;
+    while client.match_history(&alice, &0, &1).is_empty() {
+        for player in [&alice, &bob] {
+            if let Ok(Ok(dice)) = client.try_roll(player, &Vec::new(&env), &false) {
+                let save = scoring(&env, &dice);
+                if !save.is_empty() {
+                    roll(&client, player, save, true);
+                }
+                turns += 1;
+            }
+        }
+        assert!(turns < 200);
+    }
+
+    let result = client.match_history(&alice, &0, &1).get(0).unwrap();
+    assert_eq!(result.outcome, Outcome::Win);
+    assert_eq!((result.stake_a, result.stake_b), (0, 0));
+    assert_eq!(result.a, bob);
+    assert_eq!((result.score_a, result.score_b, turns), (3750, 2250, 12));
+    assert_eq!(client.pending_rewards(&alice), 0);
+    assert_eq!(client.pending_rewards(&bob), 0);
+
+    // Nor does practice count toward ratings or records.
+    for player in [&alice, &bob] {
+        assert_eq!(client.rating(player), rating::DEFAULT_RATING);
+        assert_eq!(client.stats(player), PlayerStats::default());
+    }
+    assert_eq!(client.head_to_head(&alice, &bob).played, 0);
+
+    // A rematch of a practice match is still free.
+    let loser = if result.a == alice { &bob } else { &alice };
+    client.offer_rematch(&result.a);
+    client.accept_rematch(loser);
+    assert_eq!(client.balance(&alice), -1); // still never deposited
+    assert_eq!(client.balance(&bob), -1);
+}
+
+#[test]
+fn test_contest() {
+    let env = seeded(0);
+    let (client, sac_client, admin, _) = setup(&env);
+    let [alice, bob] = players(&env, &client, &sac_client);
+    let closes = env.ledger().sequence() + 100;
+
+    let payouts = vec![&env, 700, 300];
+    assert_eq!(
+        client.try_open_contest(&COST_TO_PLAY, &3, &closes, &vec![&env, 700, 200]),
+        Err(Ok(Error::InvalidAmount.into()))
+    );
+    // First place must get something, or a short leaderboard has no shares.
+    assert_eq!(
+        client.try_open_contest(&COST_TO_PLAY, &3, &closes, &vec![&env, 0, 0, 1000]),
+        Err(Ok(Error::InvalidAmount.into()))
+    );
+    let id = client.open_contest(&COST_TO_PLAY, &3, &closes, &payouts);
+
+    for who in [&alice, &bob] {
+        client.enter_contest(who, &id);
+        assert_eq!(
+            client.try_enter_contest(who, &id),
+            Err(Ok(Error::AlreadyPlaying.into()))
+        );
+
+        // Each player banks whatever they first roll, three times over.
+        while client.contest_entry(&id, who).unwrap().playing {
+            let dice = client.contest_roll(who, &id, &Vec::new(&env), &false);
+            let save = scoring(&env, &dice);
+            if !save.is_empty() {
+                // No keeping the same die twice.
+                let twice = vec![&env, save.get(0).unwrap(), save.get(0).unwrap()];
+                assert_eq!(
+                    client.try_contest_roll(who, &id, &twice, &true),
+                    Err(Ok(Error::BadDieHold.into()))
+                );
+                client.contest_roll(who, &id, &save, &true);
+            }
+        }
+        assert_eq!(client.contest_entry(&id, who).unwrap().run.turns, 3);
+    }
+
+    // Nobody finishes this one, so its pool goes to the admin.
+    let empty = client.open_contest(&COST_TO_PLAY, &3, &closes, &payouts);
+    client.enter_contest(&alice, &empty);
+
+    let contest = client.contest(&id);
+    let pool = 2 * (COST_TO_PLAY - Farkle::get_fee(COST_TO_PLAY));
+    assert_eq!(contest.pool, pool);
+    assert_eq!(contest.leaders.len(), 2);
+    let first = contest.leaders.get(0).unwrap();
+    let second = contest.leaders.get(1).unwrap();
+    assert_eq!((first.player.clone(), first.score), (alice.clone(), 1450));
+    assert_eq!((second.player.clone(), second.score), (bob.clone(), 1350));
+
+    assert_eq!(
+        client.try_close_contest(&id),
+        Err(Ok(Error::ContestOpen.into()))
+    );
+    env.ledger().set_sequence_number(closes + 1);
+    assert_eq!(
+        client.try_enter_contest(&alice, &id),
+        Err(Ok(Error::ContestClosed.into()))
+    );
+
+    client.close_contest(&id);
+    assert_eq!(sac_client.balance(&first.player), INIT / 2 + pool * 7 / 10);
+    assert_eq!(sac_client.balance(&second.player), INIT / 2 + pool * 3 / 10);
+    assert!(client.contest(&id).paid);
+
+    let before = sac_client.balance(&admin);
+    let contest = client.close_contest(&empty);
+    assert!(contest.paid);
+    assert_eq!(sac_client.balance(&admin), before + pool / 2);
+    assert_eq!(
+        client.try_close_contest(&empty),
+        Err(Ok(Error::ContestClosed.into()))
+    );
+}
+
+#[test]
+fn test_daily() {
+    let env = seeded(0);
+    let (client, _, _, _) = setup(&env);
+    let [alice, bob, carol] = core::array::from_fn(|_| Address::generate(&env));
+    let day = client.today() + 1;
+
+    // The admin commits to tomorrow's seed, and can't change it afterwards.
+    let seed = BytesN::from_array(&env, &[7; 32]);
+    let hash: BytesN<32> = env.crypto().sha256(&Bytes::from(&seed)).into();
+    assert_eq!(
+        client.try_commit_daily(&(day - 1), &hash),
+        Err(Ok(Error::ContestOpen.into()))
+    );
+    client.commit_daily(&day, &hash);
+    assert_eq!(
+        client.try_commit_daily(&day, &hash),
+        Err(Ok(Error::ContestOpen.into()))
+    );
+    assert_eq!(
+        client.try_submit_daily(&alice, &Vec::new(&env)),
+        Err(Ok(Error::ContestClosed.into()))
+    );
+    env.ledger()
+        .set_timestamp(env.ledger().timestamp() + 60 * 60 * 24);
+
+    // Dealt from the seed, Alice banks whatever scores on each turn's first
+    // roll. Bob stops after two turns, and Carol keeps a die that doesn't
+    // score.
+    let mut moves = Vec::new(&env);
+    for index in 0..daily::DAILY_TURNS {
+        moves.push_back((Vec::new(&env), false));
+        let save = scoring(&env, &client.seeded_dice(&seed, &index, &6));
+        if !save.is_empty() {
+            moves.push_back((save, true));
+        }
+    }
+    client.submit_daily(&alice, &moves);
+    assert_eq!(
+        client.try_submit_daily(&alice, &moves),
+        Err(Ok(Error::AlreadyPlaying.into()))
+    );
+    client.submit_daily(&bob, &moves.slice(..3));
+    let keep = vec![&env, (Vec::new(&env), false), (vec![&env, 0], true)];
+    assert_eq!(client.seeded_dice(&seed, &0, &6).get(0), Some(2));
+    client.submit_daily(&carol, &keep);
+
+    // Nothing is scored, and the seed is nowhere on the ledger, until the
+    // day is over.
+    assert_eq!(
+        client.try_reveal_daily(&day, &seed),
+        Err(Ok(Error::ContestOpen.into()))
+    );
+    assert_eq!(
+        client.try_check_daily(&day, &alice),
+        Err(Ok(Error::ContestOpen.into()))
+    );
+    assert_eq!(client.daily_seed(&day), None);
+    for (_, entry) in env.to_ledger_snapshot().ledger_entries {
+        let entry = entry.0.to_xdr(Limits::none()).unwrap();
+        assert!(!entry.windows(32).any(|w| w == seed.to_array()));
+    }
+
+    env.ledger()
+        .set_timestamp(env.ledger().timestamp() + 60 * 60 * 24);
+    assert_eq!(
+        client.try_reveal_daily(&day, &BytesN::from_array(&env, &[8; 32])),
+        Err(Ok(Error::BadSeed.into()))
+    );
+    client.reveal_daily(&day, &seed);
+    assert_eq!(client.daily_seed(&day), Some(seed));
+
+    // Once it's revealed, anyone can have a run checked against the seed.
+    assert_eq!(client.check_daily(&day, &alice), 2350);
+    let entry = client.daily_entry(&day, &alice).unwrap();
+    assert!(entry.checked);
+    assert_eq!(entry.run.turns, daily::DAILY_TURNS);
+    assert_eq!(
+        client.try_check_daily(&day, &alice),
+        Err(Ok(Error::WrongMatch.into()))
+    );
+    assert_eq!(
+        client.try_check_daily(&day, &bob),
+        Err(Ok(Error::WrongMatch.into()))
+    );
+    assert_eq!(
+        client.try_check_daily(&day, &carol),
+        Err(Ok(Error::BadDieHold.into()))
+    );
+
+    let board = client.daily_board(&day);
+    assert_eq!(board.len(), 1);
+    assert_eq!(board.get(0).unwrap().player, alice);
+    assert_eq!(board.get(0).unwrap().score, 2350);
+}
+
+#[test]
+fn test_tournament() {
+    let env = seeded(0);
+    let (client, sac_client, _, _) = setup(&env);
+    let players: [Address; 5] = players(&env, &client, &sac_client);
+    env.ledger().with_mut(|l| l.min_temp_entry_ttl = 1000); // outlive a round
+
+    // The champion, the runner-up, then each semifinalist.
+    let payouts = vec![&env, 600, 200, 100];
+    let id = client.open_tournament(&COST_TO_PLAY, &8, &100, &payouts);
+    for p in &players {
+        client.register_tournament(p, &id);
+    }
+    assert_eq!(
+        client.tournament_status(&id, &players[0]),
+        TournamentStatus::Registered
+    );
+
+    // Everyone has the same rating, so seeds go by registration. The top
+    // three get byes, and only 4 v 5 is played in the first round.
+    let t = client.start_tournament(&id);
+    let first = t.rounds.get(0).unwrap();
+    assert_eq!(first.len(), 4);
+    assert_eq!(first.get(1).unwrap().a, players[3]);
+    assert_eq!(first.get(1).unwrap().b, Some(players[4].clone()));
+    assert_eq!(first.get(0).unwrap().winner, Some(players[0].clone()));
+
+    // The lower seed concedes each match, until the final.
+    client.concede(&players[4]);
+    assert_eq!(
+        client.tournament_status(&id, &players[4]),
+        TournamentStatus::Eliminated(1)
+    );
+    assert_eq!(client.tournament(&id).rounds.len(), 2);
+    client.concede(&players[3]);
+    client.concede(&players[2]);
+
+    // Nobody gets anywhere in the final, so it goes to the better seed once
+    // it's due, without counting as a forfeit or moving either rating.
+    let t = client.tournament(&id);
+    assert_eq!(t.rounds.len(), 3);
+    assert_eq!(
+        client.try_advance_tournament(&id),
+        Err(Ok(Error::RoundInProgress.into()))
+    );
+    let ratings = (client.rating(&players[0]), client.rating(&players[1]));
+    env.ledger().set_sequence_number(t.deadline + 1);
+    let t = client.advance_tournament(&id);
+    assert_eq!(t.champion, Some(players[0].clone()));
+    let result = client.match_history(&players[1], &0, &1).get(0).unwrap();
+    assert_eq!(result.outcome, Outcome::Deadline);
+    assert_eq!(client.stats(&players[1]).forfeited, 0);
+    assert_eq!(
+        (client.rating(&players[0]), client.rating(&players[1])),
+        ratings
+    );
+    assert_eq!(
+        client.tournament_status(&id, &players[1]),
+        TournamentStatus::Eliminated(3)
+    );
+
+    let pool = 5 * (COST_TO_PLAY - Farkle::get_fee(COST_TO_PLAY));
+    assert_eq!(t.pool, pool);
+    assert_eq!(sac_client.balance(&players[1]), INIT / 2 + pool / 5);
+    assert_eq!(sac_client.balance(&players[2]), INIT / 2 + pool / 10);
+    assert_eq!(sac_client.balance(&players[3]), INIT / 2 + pool / 10);
+    assert_eq!(sac_client.balance(&players[4]), INIT / 2);
+    assert_eq!(sac_client.balance(&players[0]), INIT / 2 + pool * 6 / 10);
+    assert_eq!(
+        client.try_cancel_tournament(&id),
+        Err(Ok(Error::TournamentStarted.into()))
+    );
+
+    // Calling off the next one ends its match and hands back the fees.
+    let id = client.open_tournament(&COST_TO_PLAY, &8, &100, &payouts);
+    client.register_tournament(&players[0], &id);
+    client.register_tournament(&players[1], &id);
+    client.start_tournament(&id);
+    assert_eq!(client.balance(&players[0]), INIT / 2 - 2 * COST_TO_PLAY);
+
+    let t = client.cancel_tournament(&id);
+    assert!(t.cancelled);
+    assert_eq!(t.pool, 0);
+    for p in &players[..2] {
+        let fee = Farkle::get_fee(COST_TO_PLAY);
+        assert_eq!(client.balance(p), INIT / 2 - COST_TO_PLAY - fee);
+    }
+    let result = client.match_history(&players[0], &0, &1).get(0).unwrap();
+    assert_eq!(result.outcome, Outcome::Aborted);
+    assert_eq!(
+        client.try_advance_tournament(&id),
+        Err(Ok(Error::TournamentStarted.into()))
+    );
+    assert_eq!(
+        client.try_register_tournament(&players[2], &id),
+        Err(Ok(Error::TournamentStarted.into()))
+    );
+    client.engage(&players[0], &players[1]);
+}
+
+#[test]
+fn test_league() {
+    let env = seeded(0);
+    let (client, sac_client, _, _) = setup(&env);
+    let players: [Address; 3] = players(&env, &client, &sac_client);
+    let entrants = Vec::from_slice(&env, &players);
+
+    // With three entrants, someone sits out each round.
+    let id = client.open_season(&entrants, &COST_TO_PLAY, &100, &vec![&env, 700, 300]);
+    let season = client.season(&id);
+    assert_eq!(season.fixtures.len(), 3);
+    let fixture = season.fixtures.get(0).unwrap();
+    assert_eq!(
+        (fixture.round, fixture.a, fixture.b),
+        (1, players[1].clone(), players[2].clone())
+    );
+
+    assert_eq!(
+        client.try_play_fixture(&id, &0),
+        Err(Ok(Error::WrongMatch.into()))
+    );
+    for p in &players {
+        client.join_season(p, &id);
+    }
+
+    // The second entrant goes first and banks what they can, then the third
+    // concedes.
+    let (first, second) = (&players[1], &players[2]);
+    assert_eq!(client.play_fixture(&id, &0), *first);
+    let dice = roll(&client, first, vec![&env], false);
+    roll(&client, first, scoring(&env, &dice), true);
+    client.concede(second);
+    assert_eq!(
+        client.try_play_fixture(&id, &0),
+        Err(Ok(Error::WrongMatch.into()))
+    );
+
+    let fixture = client.season(&id).fixtures.get(0).unwrap();
+    assert_eq!(fixture.winner, Some(first.clone()));
+    assert_eq!((fixture.a_score, fixture.b_score), (150, 0));
+    let banked = 150;
+
+    // The first entrant beats both of the others.
+    client.play_fixture(&id, &1);
+    client.concede(&players[2]);
+    client.play_fixture(&id, &2);
+    client.concede(&players[1]);
+
+    let table = client.season_standings(&id);
+    let top = table.get(0).unwrap();
+    assert_eq!(
+        (top.player, top.points, top.wins, top.played),
+        (players[0].clone(), 4, 2, 2)
+    );
+    let runner_up = table.get(1).unwrap();
+    assert_eq!((runner_up.player, runner_up.diff), (first.clone(), banked));
+    assert_eq!(table.get(2).unwrap().diff, -banked);
+
+    assert_eq!(
+        client.try_close_season(&id),
+        Err(Ok(Error::SeasonInProgress.into()))
+    );
+    env.ledger().set_sequence_number(101);
+    assert_eq!(
+        client.try_play_fixture(&id, &0),
+        Err(Ok(Error::SeasonOver.into()))
+    );
+    client.close_season(&id);
+
+    let pool = 3 * (COST_TO_PLAY - Farkle::get_fee(COST_TO_PLAY));
+    assert_eq!(
+        sac_client.balance(&players[0]),
+        INIT / 2 + pool - pool * 3 / 10
+    );
+    assert_eq!(sac_client.balance(first), INIT / 2 + pool * 3 / 10);
+    assert_eq!(sac_client.balance(second), INIT / 2);
+    assert_eq!(
+        client.try_close_season(&id),
+        Err(Ok(Error::SeasonOver.into()))
+    );
+}
+
+/** A strategy that keeps every scoring die and banks at 300 points. */
+#[contract]
+struct Banker;
+
+#[contractimpl]
+impl FarkleStrategy for Banker {
+    fn decide(env: Env, state: BotState) -> (Vec<u32>, bool) {
+        let save = scoring(&env, &state.dice);
+        let left = state.dice.len() - save.len();
+        (save, state.turn_score >= 300 || (left > 0 && left < 3))
+    }
+}
+
+#[test]
+fn test_bots() {
+    let env = seeded(0);
+    let (client, sac_client, admin, _) = setup(&env);
+    let alice = player(&env, &client, &sac_client);
+    let a = env.register(Banker, ());
+    let b = env.register(Banker, ());
+
+    assert_eq!(
+        client.try_engage_bot(&alice, &a),
+        Err(Ok(Error::NotABot.into()))
+    );
+    client.register_bot(&a, &true);
+    client.register_bot(&b, &true);
+    assert!(client.is_bot(&a));
+
+    // A practice game against a bot costs nothing.
+    client.engage_bot(&alice, &a);
+    assert_eq!(client.balance(&alice), INIT / 2);
+    client.concede(&alice);
+    assert_eq!(client.balance(&alice), INIT / 2);
+
+    // Only the admin can claim a stalled match on a bot's behalf.
+    env.ledger().with_mut(|l| {
+        l.sequence_number = 100;
+        l.min_temp_entry_ttl = 1000; // so the match outlives the wait
+    });
+    if client.engage_bot(&alice, &a) == a {
+        client.advance_bot(&a);
+    }
+    env.ledger().set_sequence_number(1000);
+    assert!(client.forfeit(&alice));
+    assert_eq!(env.auths()[0].0, admin);
+    assert_eq!(client.stats(&alice).forfeited, 1);
+
+    // Two bots play each other with nobody but a caller to move them along.
+    let first = client.engage_bots(&a, &b);
+    let second = if first == a { b.clone() } else { a.clone() };
+    assert_eq!(
+        client.try_advance_bot(&second),
+        Err(Ok(Error::NotYourTurn.into()))
+    );
+
+    let mut turns = 0;
+    while client.match_history(&a, &0, &3).len() < 3 {
+        for bot in [&a, &b] {
+            let rv = client.try_advance_bot(bot);
+            if rv.is_ok() {
+                turns += 1;
+            }
+        }
+        assert!(turns < 200);
+    }
+
+    let result = client.match_history(&a, &0, &1).get(0).unwrap();
+    assert_eq!(result.outcome, Outcome::Win);
+    assert_eq!(result.a, b);
+    assert_eq!((result.score_a, result.score_b, turns), (3600, 1600, 9));
+    assert_eq!(client.stats(&a).played, 3);
+
+    client.register_bot(&a, &false);
+    assert!(!client.is_bot(&a));
+}
+
+/** Picks out the indices of the dice in `roll` that score. */
+fn scoring(env: &Env, roll: &Vec<u32>) -> Vec<u32> {
+    let mut counts = [0u32; 7];
+    for die in roll.iter() {
+        counts[die as usize] += 1;
+    }
+
+    let mut save = Vec::new(env);
+    for (i, die) in roll.iter().enumerate() {
+        if die == 1 || die == 5 || counts[die as usize] >= 3 {
+            save.push_back(i as u32);
+        }
+    }
+    save
+}
+
+/** Plays a match between `a` and `b` to the end, returning the winner. */
+fn play_out(env: &Env, client: &FarkleClient, a: &Address, b: &Address) -> Address {
+    let played = client.match_history(a, &0, &100).len();
+    client.engage(a, b);
+
+    let mut turns = 0;
+    while client.match_history(a, &0, &100).len() == played {
+        for player in [a, b] {
+            if let Ok(Ok(dice)) = client.try_roll(player, &Vec::new(env), &false) {
+                let save = scoring(env, &dice);
+                if !save.is_empty() {
+                    roll(client, player, save, true);
+                }
+                turns += 1;
+            }
+        }
+        assert!(turns < 200);
+    }
+
+    let result = client.match_history(a, &0, &1).get(0).unwrap();
+    result.winner.unwrap()
+}
+
+/**
+ * Creates a test environment whose dice (and coin tosses for who goes first)
+ * are drawn from `seed`, so every run of a test plays out the same way. Only
+ * the baseline `test` leaves a snapshot behind.
+ */
+fn seeded(seed: u8) -> Env {
+    let env = Env::new_with_config(EnvTestConfig {
+        capture_snapshot_at_drop: false,
+    });
+    env.host().set_base_prng_seed([seed; 32]).unwrap();
+    env
+}
+
+/** Creates `N` players with funds deposited into the game. */
+fn players<const N: usize>(env: &Env, client: &FarkleClient, xlm: &token::Client) -> [Address; N] {
+    core::array::from_fn(|_| player(env, client, xlm))
+}
+
+/** Creates a player with funds deposited into the game. */
+fn player(env: &Env, client: &FarkleClient, xlm: &token::Client) -> Address {
+    let who = Address::generate(env);
+    fund(env, &xlm.address, &who, INIT);
+    client.deposit(&who, &(INIT / 2));
+    who
 }
 
 fn roll(client: &FarkleClient, player: &Address, roll: Vec<u32>, stop: bool) -> Vec<u32> {
     let rv = client.roll(player, &roll, &stop);
     std::println!("Resulting roll: {rv:?}");
-    return rv;
+    rv
 }
@@ -0,0 +1,588 @@
+//! Single-elimination bracket tournaments.
+//!
+//! The admin opens a tournament, players register by paying its entry fee,
+//! and once the admin starts it, entrants are seeded by rating into a bracket
+//! (with byes going to the top seeds). Every round's matches are started
+//! automatically and played through `roll` like any other match, and winners
+//! move on as soon as their match ends.
+//!
+//! Each round has a deadline. After it, anyone may `advance_tournament`: a
+//! match still being played goes to whoever is ahead (without counting as a
+//! forfeit, or towards anyone's rating), and a pairing that never got played
+//! (say, because a player was busy in another match when the round began)
+//! goes to the better seed.
+//!
+//! Until there's a champion, the admin can `cancel_tournament` to call it
+//! off and refund the entry fees.
+
+use soroban_sdk::{contractevent, contractimpl, contracttype, panic_with_error, token};
+use soroban_sdk::{Address, Env, Vec};
+
+use crate::{
+    head_to_head, history, persist, rating, AdminData, Error, Farkle, FarkleArgs, FarkleClient,
+    MatchTerms, Outcome, TournamentData, UserData,
+};
+
+const MAX_SIZE: u32 = 64;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Tournament {
+    pub id: u64,
+    pub fee: i128,
+    pub size: u32,                 // the most players who may register
+    pub round_length: u32,         // ledgers each round has to be played in
+    pub payouts: Vec<u32>,         // per-mille prize for each tier (see `open_tournament`)
+    pub pool: i128,                // entry fees, less the usual fee
+    pub entrants: Vec<Address>,    // in the order they registered
+    pub rounds: Vec<Vec<Pairing>>, // the bracket so far, empty until it starts
+    pub deadline: u32,             // ledger the current round should be over by
+    pub champion: Option<Address>,
+    pub cancelled: bool,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Pairing {
+    pub a: Address,         // the better seed
+    pub b: Option<Address>, // none for a bye
+    pub winner: Option<Address>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum TournamentStatus {
+    Out,             // hasn't registered
+    Registered,      // waiting for the tournament to start
+    Playing(u32),    // still in, as of this round (counting from 1)
+    Eliminated(u32), // knocked out in this round
+    Champion,
+}
+
+#[contractevent(topics = ["tournament_round"], data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TournamentRoundEvent {
+    #[topic]
+    id: u64,
+
+    round: u32,
+}
+
+#[contractevent(topics = ["tournament_prize"], data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TournamentPrizeEvent {
+    #[topic]
+    id: u64,
+
+    #[topic]
+    player: Address,
+
+    prize: i128,
+}
+
+#[contractimpl]
+impl Farkle {
+    /**
+     * Opens a tournament for registration.
+     *
+     * # Arguments
+     *
+     * `fee` - What it costs to register, paid from the player's balance.
+     * `size` - The most players who may register, up to 64.
+     * `round_length` - How many ledgers each round has to be played in.
+     * `payouts` - The share of the pool (per mille) for the champion, then
+     *      the runner-up, then *each* player knocked out in the semifinals,
+     *      then each one knocked out in the quarterfinals, and so on. All of
+     *      these must add up to 1000. If there are fewer rounds than tiers,
+     *      the champion takes what's left over.
+     *
+     * # Returns
+     *
+     * The identifier of the tournament.
+     */
+    pub fn open_tournament(
+        env: Env,
+        fee: i128,
+        size: u32,
+        round_length: u32,
+        payouts: Vec<u32>,
+    ) -> u64 {
+        Self::check_init(&env);
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        let total: u32 = payouts
+            .iter()
+            .enumerate()
+            .map(|(tier, share)| share * players_in_tier(tier as u32))
+            .sum();
+        if fee < 0
+            || !(2..=MAX_SIZE).contains(&size)
+            || round_length == 0
+            || payouts.len() > rounds_for(size) + 1
+            || total != 1000
+        {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        let instance = env.storage().instance();
+        let id: u64 = instance.get(&TournamentData::Count).unwrap_or(0);
+        instance.set(&TournamentData::Count, &(id + 1));
+
+        set(
+            &env,
+            &Tournament {
+                id,
+                fee,
+                size,
+                round_length,
+                payouts,
+                pool: 0,
+                entrants: Vec::new(&env),
+                rounds: Vec::new(&env),
+                deadline: 0,
+                champion: None,
+                cancelled: false,
+            },
+        );
+        id
+    }
+
+    /**
+     * Registers a player for a tournament, paying its entry fee.
+     *
+     * # Panics
+     *
+     * - If the tournament has started, or is full.
+     * - If the player has already registered.
+     * - If they don't have the fee deposited.
+     */
+    pub fn register_tournament(env: Env, player: Address, id: u64) {
+        Self::check_init(&env);
+        let mut t = get(&env, id);
+
+        if !t.rounds.is_empty() || t.cancelled {
+            panic_with_error!(&env, Error::TournamentStarted);
+        }
+        if t.entrants.len() >= t.size {
+            panic_with_error!(&env, Error::TournamentFull);
+        }
+        if t.entrants.contains(&player) {
+            panic_with_error!(&env, Error::AlreadyPlaying);
+        }
+
+        player.require_auth();
+        if t.fee > 0 {
+            Self::hold_balance(&env, &player, t.fee);
+            t.pool += t.fee - Self::get_fee(t.fee);
+        }
+
+        t.entrants.push_back(player);
+        set(&env, &t);
+    }
+
+    /**
+     * Closes registration, seeds the bracket and starts the first round.
+     *
+     * # Panics
+     *
+     * - If the tournament has already started.
+     * - If fewer than two players have registered.
+     */
+    pub fn start_tournament(env: Env, id: u64) -> Tournament {
+        Self::check_init(&env);
+        let mut t = get(&env, id);
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        if !t.rounds.is_empty() || t.cancelled {
+            panic_with_error!(&env, Error::TournamentStarted);
+        }
+        if t.entrants.len() < 2 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        let seeds = seed(&env, &t.entrants);
+        let mut first = Vec::new(&env);
+        for (a, b) in bracket_order(rounds_for(seeds.len())) {
+            first.push_back(Pairing {
+                a: seeds.get(a - 1).unwrap(),
+                b: seeds.get(b - 1),
+                winner: None,
+            });
+        }
+
+        t.rounds.push_back(first);
+        start_round(&env, &mut t);
+        progress(&env, &mut t);
+        set(&env, &t);
+        t
+    }
+
+    /**
+     * Settles every pairing left in the current round once its deadline has
+     * passed, moving the tournament on. Anyone may call this.
+     *
+     * # Panics
+     *
+     * - If the tournament hasn't started, is over, or was cancelled.
+     * - If the round's deadline hasn't passed yet.
+     */
+    pub fn advance_tournament(env: Env, id: u64) -> Tournament {
+        Self::check_init(&env);
+        let t = get(&env, id);
+        if t.rounds.is_empty() || t.champion.is_some() || t.cancelled {
+            panic_with_error!(&env, Error::TournamentStarted);
+        }
+        if env.ledger().sequence() <= t.deadline {
+            panic_with_error!(&env, Error::RoundInProgress);
+        }
+
+        let round = t.rounds.len();
+        loop {
+            let mut t = get(&env, id);
+            if t.rounds.len() != round || t.champion.is_some() {
+                return t;
+            }
+
+            let pairings = t.rounds.last().unwrap();
+            let Some((i, pairing)) = pairings
+                .iter()
+                .enumerate()
+                .find(|(_, p)| p.winner.is_none())
+            else {
+                return t;
+            };
+            let b = pairing.b.clone().unwrap();
+
+            if playing(&env, id, &pairing.a, &b) {
+                // Whoever's behind loses, or the worse seed on a tie.
+                let a_score = Self::banked(&env, &pairing.a, &b);
+                let b_score = Self::banked(&env, &b, &pairing.a);
+                let (winner, loser, score) = if a_score >= b_score {
+                    (&pairing.a, &b, a_score)
+                } else {
+                    (&b, &pairing.a, b_score)
+                };
+                Self::settle(&env, winner, loser, score, Outcome::Deadline);
+            } else {
+                decide(&env, &mut t, i as u32, pairing.a);
+                progress(&env, &mut t);
+                set(&env, &t);
+            }
+        }
+    }
+
+    /**
+     * Calls off a tournament before it has a champion, ending any of its
+     * matches still being played and refunding each entrant's fee (less the
+     * usual fee already taken from it). Only the admin may do this.
+     *
+     * # Panics
+     *
+     * - If the tournament is over, or was already cancelled.
+     */
+    pub fn cancel_tournament(env: Env, id: u64) -> Tournament {
+        Self::check_init(&env);
+        let mut t = get(&env, id);
+
+        let admin: Address = env.storage().instance().get(&AdminData::Admin).unwrap();
+        admin.require_auth();
+
+        if t.champion.is_some() || t.cancelled {
+            panic_with_error!(&env, Error::TournamentStarted);
+        }
+
+        if let Some(pairings) = t.rounds.last() {
+            for p in pairings.iter() {
+                let Some(b) = p.b else {
+                    continue;
+                };
+                if p.winner.is_none() && playing(&env, id, &p.a, &b) {
+                    history::record(&env, &p.a, &b, None, Outcome::Aborted, 0);
+                    head_to_head::record(&env, &p.a, &b, None, Outcome::Aborted);
+                    unlink(&env, &p.a, &b);
+                    Self::_end_match(&env, p.a, b);
+                }
+            }
+        }
+
+        let refund = t.fee - Self::get_fee(t.fee);
+        if refund > 0 {
+            for player in t.entrants.iter() {
+                Self::credit(&env, &player, refund);
+            }
+        }
+        t.pool = 0;
+        t.cancelled = true;
+        set(&env, &t);
+        t
+    }
+
+    /** Returns a tournament, including its bracket. */
+    pub fn tournament(env: Env, id: u64) -> Tournament {
+        get(&env, id)
+    }
+
+    /** Returns how far a player has come in a tournament. */
+    pub fn tournament_status(env: Env, id: u64, player: Address) -> TournamentStatus {
+        let t = get(&env, id);
+        if !t.entrants.contains(&player) {
+            return TournamentStatus::Out;
+        }
+        if t.rounds.is_empty() {
+            return TournamentStatus::Registered;
+        }
+        if t.champion.as_ref() == Some(&player) {
+            return TournamentStatus::Champion;
+        }
+
+        for (i, round) in t.rounds.iter().enumerate() {
+            for p in round.iter() {
+                let lost = (p.a == player || p.b.as_ref() == Some(&player))
+                    && p.winner.as_ref().is_some_and(|w| *w != player);
+                if lost {
+                    return TournamentStatus::Eliminated(i as u32 + 1);
+                }
+            }
+        }
+        TournamentStatus::Playing(t.rounds.len())
+    }
+}
+
+/** Returns the tournament `player` is currently playing a match of, if any. */
+pub fn current(env: &Env, player: &Address) -> Option<u64> {
+    env.storage()
+        .temporary()
+        .get(&UserData::Tournament(player.clone()))
+}
+
+/**
+ * Records the end of a match in tournament `id`, which has already been
+ * closed out, moving the winner on.
+ */
+pub fn record_game(env: &Env, id: u64, winner: &Address, loser: &Address) {
+    unlink(env, winner, loser);
+
+    let mut t = get(env, id);
+    let pairings = t.rounds.last().unwrap();
+    for (i, p) in pairings.iter().enumerate() {
+        if p.winner.is_none() && (p.a == *winner || p.b.as_ref() == Some(winner)) {
+            decide(env, &mut t, i as u32, winner.clone());
+            break;
+        }
+    }
+
+    progress(env, &mut t);
+    set(env, &t);
+}
+
+/** Detaches a match from its tournament, e.g. when the admin ends it. */
+pub fn unlink(env: &Env, a: &Address, b: &Address) {
+    let store = env.storage().temporary();
+    for player in [a, b] {
+        let key = UserData::Tournament(player.clone());
+        if store.has(&key) {
+            store.remove(&key);
+        }
+    }
+}
+
+fn get(env: &Env, id: u64) -> Tournament {
+    match env
+        .storage()
+        .persistent()
+        .get(&TournamentData::Tournament(id))
+    {
+        Some(t) => t,
+        None => panic_with_error!(env, Error::WrongMatch),
+    }
+}
+
+/** Whether `a` and `b` are in the middle of their match in tournament `id`. */
+fn playing(env: &Env, id: u64, a: &Address, b: &Address) -> bool {
+    let store = env.storage().temporary();
+    let opp: Option<Address> = store.get(&UserData::Match(a.clone()));
+    opp.as_ref() == Some(b) && current(env, a) == Some(id)
+}
+
+fn decide(env: &Env, t: &mut Tournament, i: u32, winner: Address) {
+    let last = t.rounds.len() - 1;
+    let mut pairings = t.rounds.get(last).unwrap();
+    let mut p = pairings.get(i).unwrap();
+    p.winner = Some(winner);
+    pairings.set(i, p);
+    t.rounds.set(last, pairings);
+
+    // Keep the storage in step, in case settling the next match reloads it.
+    set(env, t);
+}
+
+/** Starts every match of the latest round, deciding those that can't be. */
+fn start_round(env: &Env, t: &mut Tournament) {
+    t.deadline = env.ledger().sequence() + t.round_length;
+
+    let last = t.rounds.len() - 1;
+    let mut pairings = t.rounds.get(last).unwrap();
+    for i in 0..pairings.len() {
+        let mut p = pairings.get(i).unwrap();
+        let Some(b) = p.b.clone() else {
+            p.winner = Some(p.a.clone()); // a bye
+            pairings.set(i, p);
+            continue;
+        };
+
+        let store = env.storage().temporary();
+        let a_free = !store.has(&UserData::Match(p.a.clone()));
+        let b_free = !store.has(&UserData::Match(b.clone()));
+        if a_free && b_free {
+            store.set(&UserData::Tournament(p.a.clone()), &t.id);
+            store.set(&UserData::Tournament(b.clone()), &t.id);
+
+            let first: u64 = env.prng().gen_range(1..=2);
+            Farkle::start_match(env, &p.a, &b, first == 1, &MatchTerms::even(0));
+        } else {
+            // Whoever's busy elsewhere forfeits the pairing.
+            p.winner = Some(if a_free || !b_free { p.a.clone() } else { b });
+            pairings.set(i, p);
+        }
+    }
+    t.rounds.set(last, pairings);
+
+    TournamentRoundEvent {
+        id: t.id,
+        round: t.rounds.len(),
+    }
+    .publish(env);
+}
+
+/** Moves on to the next round (or pays out) for as long as rounds are done. */
+fn progress(env: &Env, t: &mut Tournament) {
+    loop {
+        let pairings = t.rounds.last().unwrap();
+        if pairings.iter().any(|p| p.winner.is_none()) {
+            return;
+        }
+
+        if pairings.len() == 1 {
+            finish(env, t, pairings.get(0).unwrap().winner.unwrap());
+            return;
+        }
+
+        let mut next = Vec::new(env);
+        for i in (0..pairings.len()).step_by(2) {
+            next.push_back(Pairing {
+                a: pairings.get(i).unwrap().winner.unwrap(),
+                b: pairings.get(i + 1).unwrap().winner,
+                winner: None,
+            });
+        }
+        t.rounds.push_back(next);
+        start_round(env, t);
+    }
+}
+
+/** Crowns the champion and pays out the prize pool. */
+fn finish(env: &Env, t: &mut Tournament, champion: Address) {
+    t.champion = Some(champion.clone());
+
+    let contract = env.current_contract_address();
+    let client = token::Client::new(env, &Farkle::token(env));
+    let rounds = t.rounds.len();
+    let mut left = t.pool;
+
+    for (i, round) in t.rounds.iter().enumerate() {
+        // Losers of the final are in tier 1, of the semifinals in tier 2...
+        let tier = rounds - i as u32;
+        let Some(share) = t.payouts.get(tier) else {
+            continue;
+        };
+
+        for p in round.iter() {
+            let Some(b) = p.b else {
+                continue;
+            };
+            let loser = if p.winner == Some(b.clone()) { p.a } else { b };
+
+            let prize = t.pool * share as i128 / 1000;
+            if prize > 0 {
+                left -= prize;
+                client.transfer(&contract, &loser, &prize);
+                TournamentPrizeEvent {
+                    id: t.id,
+                    player: loser,
+                    prize,
+                }
+                .publish(env);
+            }
+        }
+    }
+
+    if left > 0 {
+        client.transfer(&contract, &champion, &left);
+    }
+    TournamentPrizeEvent {
+        id: t.id,
+        player: champion,
+        prize: left,
+    }
+    .publish(env);
+}
+
+/** Orders entrants by rating, best first, keeping ties in registration order. */
+fn seed(env: &Env, entrants: &Vec<Address>) -> Vec<Address> {
+    let mut seeds: Vec<Address> = Vec::new(env);
+    let mut ratings: Vec<u32> = Vec::new(env);
+    for player in entrants.iter() {
+        let r = rating::get(env, &player);
+        let at = ratings
+            .iter()
+            .position(|x| x < r)
+            .unwrap_or(ratings.len() as usize) as u32;
+        seeds.insert(at, player);
+        ratings.insert(at, r);
+    }
+    seeds
+}
+
+/**
+ * Returns the first round's pairings of seeds (counting from 1) for a
+ * bracket of `rounds` rounds, such that the top seeds meet as late as
+ * possible: 1 v 8, 4 v 5, 2 v 7, 3 v 6, and so on.
+ */
+fn bracket_order(rounds: u32) -> impl Iterator<Item = (u32, u32)> {
+    let mut order: [u32; MAX_SIZE as usize] = [0; MAX_SIZE as usize];
+    order[0] = 1;
+
+    let mut n = 1;
+    for _ in 0..rounds {
+        // Each seed in place is joined by the one it would meet this round.
+        for i in (0..n).rev() {
+            order[2 * i + 1] = 2 * n as u32 + 1 - order[i];
+            order[2 * i] = order[i];
+        }
+        n *= 2;
+    }
+
+    (0..n).step_by(2).map(move |i| (order[i], order[i + 1]))
+}
+
+/** How many rounds it takes to get through a bracket of `players`. */
+fn rounds_for(players: u32) -> u32 {
+    players.next_power_of_two().trailing_zeros()
+}
+
+/** How many players each share of `payouts` goes to (see `open_tournament`). */
+fn players_in_tier(tier: u32) -> u32 {
+    if tier == 0 {
+        1
+    } else {
+        1 << (tier - 1)
+    }
+}
+
+fn set(env: &Env, t: &Tournament) {
+    persist(env, &TournamentData::Tournament(t.id), t);
+}
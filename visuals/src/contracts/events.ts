@@ -31,6 +31,8 @@ export interface BustEvent extends FarkleEvent {
 export interface WinEvent extends FarkleEvent {
   type: "win";
   score: number;
+  ratingGain: number;
+  ratingLoss: number;
 }
 
 export function convertEvent(
@@ -76,7 +78,9 @@ export function convertEvent(
       return {
         type: t[0],
         player: t[1],
-        score: v,
+        score: v[0],
+        ratingGain: v[1],
+        ratingLoss: v[2],
       } as WinEvent;
 
     default: